@@ -1,13 +1,17 @@
 use anyhow::Context;
 use connect::bluetooth::scan::Scanner;
-use connect::logging;
-use crossbeam_channel::{select, unbounded};
+use connect::{logging, storage};
+use crossbeam_channel::select;
 use esp_idf_hal::peripherals::Peripherals;
 use esp_idf_hal::task::block_on;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use log::{LevelFilter, error, info};
 use positioning::beacon::{BeaconId, Output};
-use positioning::offline::Locator;
-use positioning::signal::{Processor, Signal};
+use positioning::clock::{Clock, MonotonicClock};
+use positioning::offline::{CalibrationTable, Locator, PositionFilter, SharedCalibration};
+use positioning::pipeline::{Overflow, Supervisor, channel};
+use positioning::signal::{Batch, Kalman, Processor, ProcessorConfig};
+use std::sync::Arc;
 use std::thread;
 
 fn main() {
@@ -20,19 +24,50 @@ fn main() {
     logging::disable_logs("NimBLE").expect("NimBLE disabled");
 
     let peripherals = Peripherals::take().unwrap();
+    let nvs = EspDefaultNvsPartition::take().unwrap();
 
-    let (bluetooth_tx, bluetooth_rx) = unbounded();
-    let (signal_tx, signal_rx) = unbounded::<Vec<Signal<BeaconId>>>();
-    let (position_tx, position_rx) = unbounded::<Output>();
+    // kept out of the firmware so a bad beacon can be fixed by writing only the NVS partition
+    let calibration = SharedCalibration::default();
+    match load_calibration(nvs) {
+        Ok(Some(table)) => {
+            info!("Loaded calibration table");
+            *calibration.write().unwrap() = table;
+        }
+        Ok(None) => info!("No calibration table, using advertised beacon parameters"),
+        Err(e) => error!("Failed to load calibration table: {:?}", e),
+    }
+
+    // no SNTP without wifi, so timestamps are derived from the monotonic clock
+    let clock: Arc<dyn Clock> = Arc::new(MonotonicClock::new());
+
+    // bounded so a stalled stage cannot exhaust the heap; stale data is dropped first
+    let (bluetooth_tx, bluetooth_rx) = channel(32, Overflow::DropOldest);
+    let (signal_tx, signal_rx) = channel::<Batch<BeaconId>>(1, Overflow::KeepLatest);
+    let (fix_tx, fix_rx) = channel::<Output>(1, Overflow::KeepLatest);
+    let (position_tx, position_rx) = channel::<Output>(1, Overflow::KeepLatest);
 
-    let signal_processor = Processor::default();
-    let signal_processor_handle = signal_processor.start(bluetooth_rx, signal_tx);
+    let processor_config = ProcessorConfig::default().clock(clock.clone());
+    let signal_processor_handle = Supervisor::default()
+        .supervise("processor supervisor", move || {
+            Processor::new(processor_config.clone(), Kalman::default())
+                .start(bluetooth_rx.clone(), signal_tx.clone())
+        })
+        .expect("Failed to start signal processor");
 
-    let locator = Locator::default();
-    let locator_thread = locator
-        .start(signal_rx, position_tx)
+    let locator_thread = Supervisor::default()
+        .supervise("locator supervisor", move || {
+            Locator::default()
+                .with_calibration(calibration.clone())
+                .start(signal_rx.clone(), fix_tx.clone())
+        })
         .expect("Failed to start locator");
 
+    let filter_thread = Supervisor::default()
+        .supervise("position filter supervisor", move || {
+            PositionFilter::default().start(fix_rx.clone(), position_tx.clone())
+        })
+        .expect("Failed to start position filter");
+
     let display_updater = thread::Builder::new()
         .name("display updater".to_string())
         .stack_size(8 * 1024)
@@ -70,7 +105,7 @@ fn main() {
         .expect("Failed to create thread");
 
     block_on(async {
-        let scanner = Scanner::new(5000i32, 100, 50);
+        let scanner = Scanner::new(5000i32, 100, 50, clock);
         scanner.scan_indefinit(bluetooth_tx).await;
     });
 
@@ -79,6 +114,11 @@ fn main() {
         Err(e) => error!("Locator thread panicked: {:?}", e),
     }
 
+    match filter_thread.join() {
+        Ok(_) => info!("Position filter thread completed successfully"),
+        Err(e) => error!("Position filter thread panicked: {:?}", e),
+    }
+
     match signal_processor_handle.join() {
         Ok(_) => info!("Signal processor thread completed successfully"),
         Err(e) => error!("Signal processor thread panicked: {:?}", e),
@@ -89,3 +129,12 @@ fn main() {
         Err(_) => error!("Display updater thread panicked"),
     }
 }
+
+/// Reads the calibration table from the `calibration` blob in the `positioning`
+/// namespace of the NVS partition, see the README.
+fn load_calibration(nvs: EspDefaultNvsPartition) -> anyhow::Result<Option<CalibrationTable>> {
+    let Some(blob) = storage::read_blob(nvs, "positioning", "calibration")? else {
+        return Ok(None);
+    };
+    Ok(Some(String::from_utf8(blob)?.parse()?))
+}
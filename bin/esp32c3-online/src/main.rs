@@ -2,15 +2,18 @@ use anyhow::Context;
 use connect::bluetooth::scan::Scanner;
 use connect::logging;
 use connect::wifi::Wifi;
-use crossbeam_channel::{select, unbounded};
+use crossbeam_channel::select;
 use esp_idf_hal::peripherals::Peripherals;
 use esp_idf_hal::task::block_on;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use log::{LevelFilter, error, info};
 use positioning::beacon::{BeaconId, Output};
+use positioning::clock::{Clock, SystemClock};
 use positioning::online::Locator;
-use positioning::signal::{Processor, Signal};
+use positioning::pipeline::{Overflow, Supervisor, channel};
+use positioning::signal::{Batch, Median, Processor, ProcessorConfig};
+use std::sync::Arc;
 use std::thread;
 
 fn main() {
@@ -36,16 +39,28 @@ fn main() {
         .expect("Error while creating wifi");
     wifi.connect(true).expect("Unable to start WIFI");
 
-    let (bluetooth_tx, bluetooth_rx) = unbounded();
-    let (signal_tx, signal_rx) = unbounded::<Vec<Signal<BeaconId>>>();
-    let (position_tx, position_rx) = unbounded::<Output>();
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
 
-    let signal_processor = Processor::default();
-    let signal_processor_handle = signal_processor.start(bluetooth_rx, signal_tx);
+    // bounded so a stalled stage cannot exhaust the heap; stale data is dropped first
+    let (bluetooth_tx, bluetooth_rx) = channel(32, Overflow::DropOldest);
+    let (signal_tx, signal_rx) = channel::<Batch<BeaconId>>(1, Overflow::KeepLatest);
+    let (position_tx, position_rx) = channel::<Output>(1, Overflow::KeepLatest);
+
+    let processor_config = ProcessorConfig::default().clock(clock.clone());
+    let signal_processor_handle = Supervisor::default()
+        .supervise("processor supervisor", move || {
+            Processor::new(processor_config.clone(), Median)
+                .start(bluetooth_rx.clone(), signal_tx.clone())
+        })
+        .expect("Failed to start signal processor");
 
     let locator = Locator::new(service_key, service_client_id, service_endpoint);
-    let locator_thread = locator
-        .start(signal_rx, position_tx)
+    let locator_thread = Supervisor::default()
+        .supervise("locator supervisor", move || {
+            locator
+                .clone()
+                .start(signal_rx.clone(), position_tx.clone())
+        })
         .expect("Failed to start locator");
 
     let display_updater = thread::Builder::new()
@@ -85,7 +100,7 @@ fn main() {
         .expect("Failed to create thread");
 
     block_on(async {
-        let scanner = Scanner::new(5000i32, 100, 50);
+        let scanner = Scanner::new(5000i32, 100, 50, clock);
         scanner.scan_indefinit(bluetooth_tx).await;
     });
 
@@ -1,28 +1,37 @@
 use crate::bluetooth::ibeacon;
-use crossbeam_channel::Sender;
 use esp32_nimble::{BLEAdvertisedData, BLEAdvertisedDevice, BLEDevice, BLEScan};
 use log::{debug, error};
 use positioning::beacon::BeaconId;
+use positioning::clock::Clock;
+use positioning::pipeline::BoundedSender;
 use positioning::signal::Signal;
+use std::sync::Arc;
 
 pub struct Scanner {
     scan_time_ms: i32,
     scan_interval_ms: u16,
     scan_window_ms: u16,
+    clock: Arc<dyn Clock>,
 }
 
 const ETH_BEACON_UUID: &str = "58793564-459c-548d-bfcc-367ffd4fcd70";
 
 impl Scanner {
-    pub fn new(scan_time_ms: i32, scan_interval_ms: u16, scan_window_ms: u16) -> Self {
+    pub fn new(
+        scan_time_ms: i32,
+        scan_interval_ms: u16,
+        scan_window_ms: u16,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         Scanner {
             scan_time_ms,
             scan_interval_ms,
             scan_window_ms,
+            clock,
         }
     }
 
-    pub async fn scan_indefinit(&self, tx: Sender<Signal<BeaconId>>) {
+    pub async fn scan_indefinit(&self, tx: BoundedSender<Signal<BeaconId>>) {
         let ble_device = BLEDevice::take();
         let mut ble_scan = BLEScan::new();
         loop {
@@ -45,6 +54,7 @@ impl Scanner {
                                 BeaconId::new(uuid, major, minor),
                                 ibeacon.power,
                                 device.rssi(),
+                                self.clock.as_ref(),
                             )) {
                                 error!("Failed to send signal: {}", e);
                             }
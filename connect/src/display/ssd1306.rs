@@ -40,16 +40,28 @@ impl Oled<'_> {
             .text_color(BinaryColor::On)
             .build();
 
+        let source = output
+            .source
+            .map_or("no fix".to_string(), |s| s.to_string());
+        let mut accuracy = output
+            .accuracy
+            .map_or("+/- ?".to_string(), |a| format!("+/- {:.1}m", a));
+        if output.poor_geometry {
+            accuracy.push('!');
+        }
+
         let content = [
-            "ETH Indoor (offline)".to_string(),
-            "".to_string(),
+            format!("ETH Indoor ({})", source),
+            format!("{}, {} beacons", accuracy, output.beacons),
             format!("{:.6}, {:.6}", pos.lat, pos.lon),
             format!(
-                "{:3}m, [{:3}]",
+                "{:.1}m/s, [{:3}]",
                 output.speed.unwrap_or(0f32),
                 output.heading.unwrap_or(0i32)
             ),
-            "".to_string(),
+            loc.floor.map_or("Floor ?".to_string(), |f| {
+                format!("Floor {} ({:+})", f, f.number())
+            }),
             format!("Room: {}", loc.identifier()),
         ];
 
@@ -1,5 +1,6 @@
 pub mod bluetooth;
 pub mod display;
 pub mod logging;
+pub mod storage;
 pub mod timer;
 pub mod wifi;
@@ -0,0 +1,21 @@
+use esp_idf_svc::nvs::{EspNvs, EspNvsPartition, NvsDefault};
+use esp_idf_sys::ESP_ERR_NVS_NOT_FOUND;
+
+/// Reads a blob from the NVS partition, `None` if the namespace or key is not set.
+pub fn read_blob(
+    nvs: EspNvsPartition<NvsDefault>,
+    namespace: &str,
+    key: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let nvs = match EspNvs::new(nvs, namespace, false) {
+        Ok(nvs) => nvs,
+        Err(e) if e.code() == ESP_ERR_NVS_NOT_FOUND => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(len) = nvs.blob_len(key)? else {
+        return Ok(None);
+    };
+
+    let mut buf = vec![0; len];
+    Ok(nvs.get_blob(key, &mut buf)?.map(<[u8]>::to_vec))
+}
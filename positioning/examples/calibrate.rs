@@ -0,0 +1,56 @@
+//! Fits path-loss calibrations from RSSI samples recorded at known distances.
+//!
+//! ```text
+//! cargo run --example calibrate --features offline --target <host> -- samples.csv [building]
+//! ```
+//!
+//! The CSV has the columns `uuid,major,minor,tx_power,rssi,distance`, an optional header
+//! and the distance in metres. The table is written to stdout in the format read by
+//! `CalibrationTable::load`, fitted per beacon unless `building` is given.
+
+use anyhow::{Context, bail};
+use positioning::beacon::BeaconId;
+use positioning::clock::SystemClock;
+use positioning::offline::{Grouping, Sample, fit_calibration};
+use positioning::signal::Signal;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        bail!("usage: calibrate <samples.csv> [beacon|building]");
+    };
+    let grouping = match args.next().as_deref() {
+        None | Some("beacon") => Grouping::Beacon,
+        Some("building") => Grouping::Building,
+        Some(other) => bail!("unknown grouping {}", other),
+    };
+
+    let content = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path))?;
+    let samples = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with("uuid"))
+        .map(|(number, line)| parse_sample(line).with_context(|| format!("line {}", number + 1)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let table = fit_calibration(&samples, grouping);
+    if table.is_empty() {
+        bail!(
+            "no calibration could be fitted from {} samples",
+            samples.len()
+        );
+    }
+    print!("{}", table);
+    Ok(())
+}
+
+fn parse_sample(line: &str) -> anyhow::Result<Sample> {
+    let fields: Vec<_> = line.split(',').map(str::trim).collect();
+    let [uuid, major, minor, tx_power, rssi, distance] = fields.as_slice() else {
+        bail!("expected 6 columns, got {}", fields.len());
+    };
+
+    let id = BeaconId::new(uuid, major.parse()?, minor.parse()?);
+    let signal = Signal::new(id, tx_power.parse()?, rssi.parse()?, &SystemClock);
+    Ok(Sample::new(signal, distance.parse()?))
+}
@@ -1,6 +1,9 @@
 use crate::geographic::Position;
+use crate::level::Level;
+use chrono::{DateTime, Utc};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BeaconId {
     pub uuid: String,
     pub major: u16,
@@ -32,25 +35,66 @@ impl Beacon {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Room {
     pub building: String,
-    pub floor: String,
+    /// `None` if the floor code could not be parsed.
+    pub floor: Option<Level>,
     pub room: String,
 }
 
 impl Room {
     pub fn identifier(&self) -> String {
-        format!("{}/{}/{}", self.building, self.floor, self.room)
+        let floor = self.floor.map_or("?".to_string(), |f| f.to_string());
+        format!("{}/{}/{}", self.building, floor, self.room)
     }
 
-    pub fn new(building: &str, floor: &str, room: &str) -> Self {
+    pub fn new(building: &str, floor: Level, room: &str) -> Self {
         Self {
             building: building.to_owned(),
-            floor: floor.to_owned(),
+            floor: Some(floor),
             room: room.to_owned(),
         }
     }
+
+    /// Nominal height in metres of the floor above level zero, if the floor is known.
+    pub fn height(&self) -> Option<f64> {
+        self.floor.map(|f| f.height(&self.building))
+    }
+}
+
+/// Algorithm which produced a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Trilateration,
+    NearestBeacon,
+    WeightedCentroid,
+    MinMax,
+    /// Only the room is trusted, the position is that of the nearest beacon.
+    RoomOnly,
+    /// Tracked across batches by the particle filter.
+    ParticleFilter,
+}
+
+/// Where a position was computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Trilaterated on the device.
+    Offline,
+    /// Returned by the location service.
+    Online,
+    /// Estimated on the device without enough beacons to trilaterate.
+    Fallback,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Offline => write!(f, "offline"),
+            Source::Online => write!(f, "online"),
+            Source::Fallback => write!(f, "fallback"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -59,6 +103,22 @@ pub struct Output {
     pub location: Room,
     pub speed: Option<f32>,
     pub heading: Option<i32>,
+    /// Horizontal accuracy radius in metres.
+    pub accuracy: Option<f64>,
+    /// Number of beacons the position was computed from.
+    pub beacons: usize,
+    /// Final cost of the solver, if one was run.
+    pub cost: Option<f64>,
+    /// Horizontal dilution of precision of the beacon geometry.
+    pub dop: Option<f64>,
+    /// Set when the position was published despite a poor geometry.
+    pub poor_geometry: bool,
+    /// Beacons left out of the fix as inconsistent with the others.
+    pub rejected: Vec<BeaconId>,
+    pub method: Option<Method>,
+    /// Reception time of the newest signal used.
+    pub timestamp: DateTime<Utc>,
+    pub source: Option<Source>,
 }
 
 impl Output {
@@ -73,6 +133,52 @@ impl Output {
             location,
             speed,
             heading,
+            ..Self::default()
         }
     }
+
+    pub fn with_accuracy(mut self, accuracy: f64) -> Self {
+        self.accuracy = Some(accuracy);
+        self
+    }
+
+    pub fn with_beacons(mut self, beacons: usize) -> Self {
+        self.beacons = beacons;
+        self
+    }
+
+    pub fn with_cost(mut self, cost: f64) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    pub fn with_dop(mut self, dop: f64) -> Self {
+        self.dop = Some(dop);
+        self
+    }
+
+    pub fn with_poor_geometry(mut self, poor_geometry: bool) -> Self {
+        self.poor_geometry = poor_geometry;
+        self
+    }
+
+    pub fn with_rejected(mut self, rejected: Vec<BeaconId>) -> Self {
+        self.rejected = rejected;
+        self
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_source(mut self, source: Source) -> Self {
+        self.source = Some(source);
+        self
+    }
 }
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Source of time for signal timestamps and window expiry.
+///
+/// `monotonic` never jumps and is used for anything measuring age; `now` is wall-clock
+/// time for reporting and only meaningful once SNTP has synchronised.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Time elapsed since a fixed origin.
+    fn monotonic(&self) -> Duration;
+}
+
+/// Process-wide origin, so that all system and monotonic clocks agree.
+fn origin() -> Instant {
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    *ORIGIN.get_or_init(Instant::now)
+}
+
+/// Wall-clock time from the system, following SNTP adjustments.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        origin().elapsed()
+    }
+}
+
+/// Derives wall-clock time from the monotonic clock, anchored at creation.
+///
+/// Timestamps stay consistent on devices which never synchronise via SNTP, even if the
+/// system time is later adjusted.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    anchor: DateTime<Utc>,
+    anchor_monotonic: Duration,
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            anchor: Utc::now(),
+            anchor_monotonic: origin().elapsed(),
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = self.monotonic().saturating_sub(self.anchor_monotonic);
+        self.anchor + chrono::Duration::from_std(elapsed).unwrap_or_default()
+    }
+
+    fn monotonic(&self) -> Duration {
+        origin().elapsed()
+    }
+}
+
+/// Clock which only moves when told to, for tests and replaying recordings.
+///
+/// Clones share their time, so a clone handed to the processor can be advanced from the
+/// outside.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    time: Arc<Mutex<(DateTime<Utc>, Duration)>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            time: Arc::new(Mutex::new((now, Duration::ZERO))),
+        }
+    }
+
+    /// Moves both the wall-clock and the monotonic time forward.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.lock().unwrap();
+        time.0 += chrono::Duration::from_std(duration).unwrap_or_default();
+        time.1 += duration;
+    }
+
+    /// Sets the wall-clock time without affecting the monotonic time, like SNTP does.
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.time.lock().unwrap().0 = now;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(DateTime::<Utc>::UNIX_EPOCH)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.time.lock().unwrap().0
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.time.lock().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_set_keeps_monotonic() {
+        let clock = ManualClock::default();
+        clock.advance(Duration::from_secs(3));
+        clock.set(Utc::now());
+
+        assert_eq!(clock.monotonic(), Duration::from_secs(3));
+        assert!(clock.now() > DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(3));
+    }
+
+    #[test]
+    fn test_clones_share_time() {
+        let clock = ManualClock::default();
+        let clone = clock.clone();
+        clock.advance(Duration::from_millis(1500));
+
+        assert_eq!(clone.monotonic(), Duration::from_millis(1500));
+    }
+}
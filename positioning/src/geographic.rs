@@ -2,11 +2,90 @@
 pub struct Position {
     pub lat: f64,
     pub lon: f64,
+    /// Height in metres above level zero of the building, see [`Level::height`].
+    ///
+    /// [`Level::height`]: crate::level::Level::height
+    pub alt: Option<f64>,
 }
 
 impl Position {
     pub fn new(lat: f64, lon: f64) -> Self {
-        Position { lat, lon }
+        Position {
+            lat,
+            lon,
+            alt: None,
+        }
+    }
+
+    pub fn with_altitude(mut self, alt: f64) -> Self {
+        self.alt = Some(alt);
+        self
+    }
+}
+
+/// Coordinates in metres east and north of a [`LocalFrame`] anchor.
+#[derive(Debug, Clone, Default, Copy, PartialEq)]
+pub struct Enu {
+    pub east: f64,
+    pub north: f64,
+}
+
+impl Enu {
+    pub fn new(east: f64, north: f64) -> Self {
+        Enu { east, north }
+    }
+
+    pub fn distance(&self, other: Enu) -> f64 {
+        (self.east - other.east).hypot(self.north - other.north)
+    }
+}
+
+/// Local tangent plane on the WGS84 ellipsoid anchored at a reference point.
+///
+/// The projection is linearised about the anchor using its radii of curvature, which
+/// keeps the error below a centimetre within a few hundred metres, i.e. a campus.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalFrame {
+    origin: Position,
+    /// Metres per radian of latitude.
+    meridian: f64,
+    /// Metres per radian of longitude.
+    parallel: f64,
+}
+
+impl LocalFrame {
+    pub fn new(origin: Position) -> Self {
+        const A: f64 = 6_378_137.0;
+        const E2: f64 = 6.694_379_990_14e-3;
+
+        let sin_lat = origin.lat.to_radians().sin();
+        let w2 = 1.0 - E2 * sin_lat * sin_lat;
+        let prime_vertical = A / w2.sqrt();
+
+        LocalFrame {
+            origin,
+            meridian: A * (1.0 - E2) / (w2 * w2.sqrt()),
+            parallel: prime_vertical * origin.lat.to_radians().cos(),
+        }
+    }
+
+    pub fn origin(&self) -> Position {
+        self.origin
+    }
+
+    pub fn to_local(&self, position: Position) -> Enu {
+        Enu {
+            east: (position.lon - self.origin.lon).to_radians() * self.parallel,
+            north: (position.lat - self.origin.lat).to_radians() * self.meridian,
+        }
+    }
+
+    pub fn to_global(&self, enu: Enu) -> Position {
+        Position {
+            lat: self.origin.lat + (enu.north / self.meridian).to_degrees(),
+            lon: self.origin.lon + (enu.east / self.parallel).to_degrees(),
+            alt: None,
+        }
     }
 }
 
@@ -52,6 +131,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_local_frame_round_trip() {
+        let frame = LocalFrame::new(Position::new(47.413310, 8.536444));
+        let b = Position::new(47.413309, 8.536520);
+
+        let local = frame.to_local(b);
+        assert!(
+            (local.distance(Enu::default()) - haversine_distance(frame.origin(), b)).abs() < 0.02
+        );
+
+        let back = frame.to_global(local);
+        assert!((back.lat - b.lat).abs() < 1e-12 && (back.lon - b.lon).abs() < 1e-12);
+    }
+
     #[test]
     fn test_different_points() {
         assert_eq!(
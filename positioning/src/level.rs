@@ -0,0 +1,233 @@
+use anyhow::{anyhow, bail};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Floor letters from the lowest up, `E` at index [`GROUND`].
+///
+/// ETH skips `I`. Letters after `Q`, such as the `Y` and `Z` of some buildings or the
+/// upper floors of OAT, do not continue the sequence and have no known level.
+const LETTERS: &[u8] = b"ABCDEFGHJKLMNOPQ";
+
+/// Index in the floor letters of the ground floor, level zero.
+const GROUND: usize = 4;
+
+/// Storey height in metres for buildings without a nominal height of their own.
+const DEFAULT_STOREY_HEIGHT: f64 = 4.0;
+
+/// How the floors of a building are lettered and how high they are.
+struct Building {
+    name: &'static str,
+    letters: &'static [u8],
+    storey_height: f64,
+}
+
+/// Buildings that differ from the usual lettering or storey height.
+const BUILDINGS: &[Building] = &[
+    // `I` instead of `J` for the floor above `H`
+    Building {
+        name: "ETL",
+        letters: b"ABCDEFGHIKLMNOPQ",
+        storey_height: DEFAULT_STOREY_HEIGHT,
+    },
+    // the historic main building has high ceilings
+    Building {
+        name: "HG",
+        letters: LETTERS,
+        storey_height: 5.0,
+    },
+];
+
+fn find_building(name: &str) -> Option<&'static Building> {
+    BUILDINGS.iter().find(|b| b.name == name)
+}
+
+/// A floor as an ordered level, parsed from the ETH floor codes.
+///
+/// ETH names floors by letter, consecutive letters being consecutive floors with `E`
+/// usually at street level, so `D` is level -1 and `F` level 1. The letter `I` is
+/// skipped, so `J` follows `H`, except in buildings such as ETL that use `I` instead
+/// of `J`. A trailing `O`, as in `EO`, marks the mezzanine above the floor. Letters
+/// whose place in a building is unknown are not parsed rather than guessed.
+///
+/// Levels compare by height only; the letter is kept for display.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    level: i8,
+    mezzanine: bool,
+    letter: u8,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            mezzanine: false,
+            letter: LETTERS[GROUND],
+        }
+    }
+}
+
+impl Level {
+    /// The floor `level` storeys above the ground floor, if it has a letter.
+    ///
+    /// Lettered as in most buildings, see [`Level::parse`] for the others.
+    pub fn new(level: i8) -> anyhow::Result<Self> {
+        let letter = (GROUND as isize)
+            .checked_add(level as isize)
+            .and_then(|i| usize::try_from(i).ok())
+            .and_then(|i| LETTERS.get(i))
+            .ok_or_else(|| anyhow!("no floor letter for level {}", level))?;
+        Ok(Self {
+            level,
+            mezzanine: false,
+            letter: *letter,
+        })
+    }
+
+    /// The mezzanine above the floor `level`.
+    pub fn mezzanine(level: i8) -> anyhow::Result<Self> {
+        Ok(Self {
+            mezzanine: true,
+            ..Self::new(level)?
+        })
+    }
+
+    /// Signed level, a mezzanine counting as half a storey above its floor.
+    pub fn number(&self) -> f64 {
+        self.level as f64 + if self.mezzanine { 0.5 } else { 0.0 }
+    }
+
+    /// Parses the floor code `code`, e.g. `E` or `EO`, of a floor in `building`.
+    pub fn parse(building: &str, code: &str) -> anyhow::Result<Self> {
+        let (letter, mezzanine) = match code.as_bytes() {
+            [letter] => (*letter, false),
+            [letter, b'O'] => (*letter, true),
+            _ => bail!("invalid floor {:?}", code),
+        };
+        let letters = find_building(building).map_or(LETTERS, |b| b.letters);
+        let index = letters
+            .iter()
+            .position(|l| *l == letter)
+            .ok_or_else(|| anyhow!("unknown floor {:?} in {}", code, building))?;
+
+        Ok(Self {
+            level: index as i8 - GROUND as i8,
+            mezzanine,
+            letter,
+        })
+    }
+
+    /// Nominal height in metres of the floor above level zero in the given building.
+    pub fn height(&self, building: &str) -> f64 {
+        self.number() * storey_height(building)
+    }
+
+    fn key(&self) -> (i8, bool) {
+        (self.level, self.mezzanine)
+    }
+}
+
+impl PartialEq for Level {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Level {}
+
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Level {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for Level {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Nominal storey height in metres of a building.
+pub fn storey_height(name: &str) -> f64 {
+    find_building(name).map_or(DEFAULT_STOREY_HEIGHT, |b| b.storey_height)
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter as char)?;
+        if self.mezzanine {
+            write!(f, "O")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_order() {
+        let levels: Vec<Level> = ["D", "E", "EO", "F", "H", "J", "K", "O", "Q"]
+            .iter()
+            .map(|s| Level::parse("CHN", s).unwrap())
+            .collect();
+
+        assert_eq!(
+            levels.iter().map(Level::number).collect::<Vec<_>>(),
+            vec![-1.0, 0.0, 0.5, 1.0, 3.0, 4.0, 5.0, 9.0, 11.0]
+        );
+        assert!(levels.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            levels.iter().map(Level::to_string).collect::<Vec<_>>(),
+            vec!["D", "E", "EO", "F", "H", "J", "K", "O", "Q"]
+        );
+        assert!(Level::parse("CHN", "e").is_err());
+        assert!(Level::parse("CHN", "E1").is_err());
+    }
+
+    #[test]
+    fn test_parse_per_building() {
+        // ETL uses `I` for the floor above `H`
+        let i = Level::parse("ETL", "I").unwrap();
+        assert_eq!(i, Level::parse("HG", "J").unwrap());
+        assert_eq!(i.to_string(), "I");
+        assert_eq!(Level::parse("ETL", "K").unwrap().number(), 5.0);
+        assert!(Level::parse("HG", "I").is_err());
+        // letters out of the sequence are not given a made-up level
+        for (building, code) in [("ML", "Z"), ("HIA", "Y"), ("OAT", "S"), ("OAT", "X")] {
+            assert!(
+                Level::parse(building, code).is_err(),
+                "{} {}",
+                building,
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_height() {
+        let level = Level::parse("HG", "G").unwrap();
+        assert_eq!(level.height("HG"), 10.0);
+        assert_eq!(level.height("CAB"), 8.0);
+        let level = Level::parse("HG", "K").unwrap();
+        assert_eq!(level.height("HG"), 25.0);
+    }
+
+    #[test]
+    fn test_new_restricted_to_letters() {
+        assert_eq!(Level::new(4).unwrap().to_string(), "J");
+        assert_eq!(Level::mezzanine(-1).unwrap().to_string(), "DO");
+        assert_eq!(Level::default().to_string(), "E");
+        assert!(Level::new(-5).is_err());
+        assert_eq!(Level::new(11).unwrap().to_string(), "Q");
+        assert!(Level::new(12).is_err());
+        assert!(Level::new(i8::MAX).is_err());
+    }
+}
@@ -1,4 +1,7 @@
+pub mod clock;
 pub mod geographic;
+pub mod level;
+pub mod pipeline;
 pub mod signal;
 
 pub mod beacon;
@@ -0,0 +1,256 @@
+use crate::beacon::{BeaconId, Room};
+use crate::level::Level;
+use anyhow::{Context, anyhow, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+/// Calibration table shared with a running locator, so it can be replaced at runtime.
+pub type SharedCalibration = Arc<RwLock<CalibrationTable>>;
+
+/// Correction of a beacon's advertised parameters. Unset fields fall back to the next
+/// broader scope.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Calibration {
+    /// Measured RSSI at one metre, replacing the advertised tx power.
+    pub tx_power: Option<i8>,
+    /// Path-loss exponent of the log-distance model.
+    pub exponent: Option<f64>,
+    /// Added to every RSSI received from the beacon, in dB.
+    pub rssi_offset: Option<i8>,
+    /// Mounting height in metres above the floor.
+    pub height: Option<f64>,
+}
+
+impl Calibration {
+    /// Fills the fields unset in `self` from `fallback`.
+    pub fn or(self, fallback: Calibration) -> Calibration {
+        Calibration {
+            tx_power: self.tx_power.or(fallback.tx_power),
+            exponent: self.exponent.or(fallback.exponent),
+            rssi_offset: self.rssi_offset.or(fallback.rssi_offset),
+            height: self.height.or(fallback.height),
+        }
+    }
+}
+
+/// Which beacons a [`Calibration`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Beacon(BeaconId),
+    Floor { building: String, floor: Level },
+    Building(String),
+}
+
+/// Calibrations keyed by beacon, with floor and building defaults.
+///
+/// The text format has one entry per line, `#` starts a comment:
+///
+/// ```text
+/// building HG exponent=3.2 height=2.8
+/// floor HG/E rssi_offset=-2
+/// beacon 58793564-459c-548d-bfcc-367ffd4fcd70/1/17 tx_power=-61 exponent=2.9
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalibrationTable {
+    entries: HashMap<Scope, Calibration>,
+}
+
+impl CalibrationTable {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read calibration {}", path.display()))?
+            .parse()
+    }
+
+    pub fn insert(&mut self, scope: Scope, calibration: Calibration) {
+        self.entries.insert(scope, calibration);
+    }
+
+    /// Calibration of a beacon, merged from the most specific scope outwards.
+    pub fn lookup(&self, id: &BeaconId, room: &Room) -> Calibration {
+        let floor = room.floor.map(|floor| Scope::Floor {
+            building: room.building.clone(),
+            floor,
+        });
+        let building = Scope::Building(room.building.clone());
+
+        [Some(Scope::Beacon(id.clone())), floor, Some(building)]
+            .iter()
+            .flatten()
+            .filter_map(|scope| self.entries.get(scope))
+            .fold(Calibration::default(), |acc, c| acc.or(*c))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl FromStr for CalibrationTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = CalibrationTable::default();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (scope, calibration) =
+                parse_entry(line).with_context(|| format!("line {}: {}", number + 1, line))?;
+            table.insert(scope, calibration);
+        }
+        Ok(table)
+    }
+}
+
+fn parse_entry(line: &str) -> anyhow::Result<(Scope, Calibration)> {
+    let mut fields = line.split_whitespace();
+    let kind = fields.next().unwrap_or_default();
+    let key = fields
+        .next()
+        .ok_or_else(|| anyhow!("missing {} key", kind))?;
+
+    let scope = match kind {
+        "beacon" => {
+            let parts: Vec<_> = key.split('/').collect();
+            let [uuid, major, minor] = parts.as_slice() else {
+                bail!("expected uuid/major/minor, got {}", key);
+            };
+            Scope::Beacon(BeaconId::new(uuid, major.parse()?, minor.parse()?))
+        }
+        "floor" => {
+            let (building, floor) = key
+                .split_once('/')
+                .ok_or_else(|| anyhow!("expected building/floor, got {}", key))?;
+            Scope::Floor {
+                building: building.to_string(),
+                floor: Level::parse(building, floor)?,
+            }
+        }
+        "building" => Scope::Building(key.to_string()),
+        _ => bail!("unknown scope {}", kind),
+    };
+
+    let mut calibration = Calibration::default();
+    for field in fields {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected name=value, got {}", field))?;
+        match name {
+            "tx_power" => calibration.tx_power = Some(value.parse()?),
+            "exponent" => calibration.exponent = Some(positive(name, value.parse()?)?),
+            "rssi_offset" => calibration.rssi_offset = Some(value.parse()?),
+            "height" => calibration.height = Some(finite(name, value.parse()?)?),
+            _ => bail!("unknown field {}", name),
+        }
+    }
+    Ok((scope, calibration))
+}
+
+fn finite(name: &str, value: f64) -> anyhow::Result<f64> {
+    if !value.is_finite() {
+        bail!("{} must be finite, got {}", name, value);
+    }
+    Ok(value)
+}
+
+fn positive(name: &str, value: f64) -> anyhow::Result<f64> {
+    if finite(name, value)? <= 0.0 {
+        bail!("{} must be positive, got {}", name, value);
+    }
+    Ok(value)
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Beacon(id) => write!(f, "beacon {}/{}/{}", id.uuid, id.major, id.minor),
+            Scope::Floor { building, floor } => write!(f, "floor {}/{}", building, floor),
+            Scope::Building(building) => write!(f, "building {}", building),
+        }
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(tx_power) = self.tx_power {
+            write!(f, " tx_power={}", tx_power)?;
+        }
+        if let Some(exponent) = self.exponent {
+            write!(f, " exponent={}", exponent)?;
+        }
+        if let Some(rssi_offset) = self.rssi_offset {
+            write!(f, " rssi_offset={}", rssi_offset)?;
+        }
+        if let Some(height) = self.height {
+            write!(f, " height={}", height)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CalibrationTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(scope, calibration)| format!("{}{}", scope, calibration))
+            .collect();
+        lines.sort();
+        lines.iter().try_for_each(|line| writeln!(f, "{}", line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "
+        # defaults
+        building HG exponent=3.2 rssi_offset=1 height=2.8
+        floor HG/E rssi_offset=-2
+        beacon uuid/1/17 tx_power=-61 height=1.2
+    ";
+
+    #[test]
+    fn test_lookup_merges_scopes() {
+        let table: CalibrationTable = TABLE.parse().unwrap();
+        let room = Room::new("HG", Level::new(0).unwrap(), "1");
+
+        let calibration = table.lookup(&BeaconId::new("uuid", 1, 17), &room);
+        assert_eq!(calibration.tx_power, Some(-61));
+        assert_eq!(calibration.exponent, Some(3.2));
+        assert_eq!(calibration.rssi_offset, Some(-2));
+        assert_eq!(calibration.height, Some(1.2));
+
+        let other = table.lookup(
+            &BeaconId::new("uuid", 1, 18),
+            &Room::new("HG", Level::new(1).unwrap(), "1"),
+        );
+        assert_eq!(other.tx_power, None);
+        assert_eq!(other.rssi_offset, Some(1));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let table: CalibrationTable = TABLE.parse().unwrap();
+        assert_eq!(
+            table.to_string().parse::<CalibrationTable>().unwrap(),
+            table
+        );
+        assert!(
+            "beacon uuid/1 tx_power=-61"
+                .parse::<CalibrationTable>()
+                .is_err()
+        );
+        for invalid in ["exponent=0", "exponent=-2.5", "exponent=NaN", "height=inf"] {
+            let line = format!("building HG {}", invalid);
+            assert!(line.parse::<CalibrationTable>().is_err(), "{}", line);
+        }
+    }
+}
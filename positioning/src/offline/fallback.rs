@@ -0,0 +1,198 @@
+use crate::beacon::Method;
+use crate::geographic::{Enu, LocalFrame, Position, haversine_distance};
+use crate::offline::trilateration::{Measurement, weighted_centroid};
+
+/// Spread in metres across the beacons below which they count as collinear.
+const MIN_SPREAD: f64 = 1.0;
+
+/// A position together with the radius in metres the tracker is expected within.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Estimate {
+    pub position: Position,
+    pub radius: f64,
+    pub method: Method,
+}
+
+/// Picks the method suited to the number and geometry of the beacons heard.
+///
+/// Two or collinear beacons only bound the position along their line, so they are
+/// intersected with min-max, or averaged if their ranges have no point in common.
+pub(crate) fn select(measurements: &[Measurement]) -> Method {
+    match measurements.len() {
+        0 | 1 => Method::NearestBeacon,
+        2 => along_line(measurements),
+        _ if spread(measurements) < MIN_SPREAD => along_line(measurements),
+        _ => Method::Trilateration,
+    }
+}
+
+fn along_line(measurements: &[Measurement]) -> Method {
+    if ranges_intersect(measurements) {
+        Method::MinMax
+    } else {
+        Method::WeightedCentroid
+    }
+}
+
+/// Whether every pair of range circles intersects, neither apart nor one inside the other.
+fn ranges_intersect(measurements: &[Measurement]) -> bool {
+    measurements.iter().enumerate().all(|(i, a)| {
+        measurements[i + 1..].iter().all(|b| {
+            let d = haversine_distance(Position::new(a.lat, a.lon), Position::new(b.lat, b.lon));
+            d <= a.distance + b.distance && d >= (a.distance - b.distance).abs()
+        })
+    })
+}
+
+/// Position of the nearest beacon, within the distance measured to it.
+pub(crate) fn nearest_beacon(measurements: &[Measurement]) -> Option<Estimate> {
+    measurements
+        .iter()
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+        .map(|m| Estimate {
+            position: Position::new(m.lat, m.lon),
+            radius: m.distance,
+            method: Method::NearestBeacon,
+        })
+}
+
+/// Weighted mean of the beacon positions, within the farthest beacon.
+pub(crate) fn centroid(measurements: &[Measurement]) -> Option<Estimate> {
+    let position = weighted_centroid(measurements)?;
+    let frame = LocalFrame::new(position);
+    let radius = measurements
+        .iter()
+        .map(|m| {
+            frame
+                .to_local(Position::new(m.lat, m.lon))
+                .distance(Enu::default())
+        })
+        .fold(0.0, f64::max);
+    Some(Estimate {
+        position,
+        radius,
+        method: Method::WeightedCentroid,
+    })
+}
+
+/// Centre of the intersection of the squares bounding each range circle.
+///
+/// Falls back to the weighted centroid if the boxes do not overlap.
+pub(crate) fn min_max(measurements: &[Measurement]) -> Option<Estimate> {
+    let origin = weighted_centroid(measurements)?;
+    let frame = LocalFrame::new(origin);
+
+    let (mut min, mut max) = (Enu::new(f64::MIN, f64::MIN), Enu::new(f64::MAX, f64::MAX));
+    for m in measurements {
+        let anchor = frame.to_local(Position::new(m.lat, m.lon));
+        min.east = min.east.max(anchor.east - m.distance);
+        min.north = min.north.max(anchor.north - m.distance);
+        max.east = max.east.min(anchor.east + m.distance);
+        max.north = max.north.min(anchor.north + m.distance);
+    }
+
+    if min.east > max.east || min.north > max.north {
+        return centroid(measurements);
+    }
+    let center = Enu::new((min.east + max.east) / 2.0, (min.north + max.north) / 2.0);
+    Some(Estimate {
+        position: frame.to_global(center),
+        radius: center.distance(max),
+        method: Method::MinMax,
+    })
+}
+
+/// Standard deviation in metres of the beacons along their narrowest direction.
+fn spread(measurements: &[Measurement]) -> f64 {
+    let Some(origin) = weighted_centroid(measurements) else {
+        return 0.0;
+    };
+    let frame = LocalFrame::new(origin);
+    let anchors: Vec<Enu> = measurements
+        .iter()
+        .map(|m| frame.to_local(Position::new(m.lat, m.lon)))
+        .collect();
+
+    let n = anchors.len() as f64;
+    let mean = Enu::new(
+        anchors.iter().map(|a| a.east).sum::<f64>() / n,
+        anchors.iter().map(|a| a.north).sum::<f64>() / n,
+    );
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    for a in &anchors {
+        let (dx, dy) = (a.east - mean.east, a.north - mean.north);
+        xx += dx * dx / n;
+        yy += dy * dy / n;
+        xy += dx * dy / n;
+    }
+
+    // smaller eigenvalue of the 2x2 covariance
+    let half_trace = (xx + yy) / 2.0;
+    let minor = half_trace - (half_trace.powi(2) - (xx * yy - xy * xy)).max(0.0).sqrt();
+    minor.max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geographic::haversine_distance;
+
+    fn measurement(frame: &LocalFrame, east: f64, north: f64, distance: f64) -> Measurement {
+        let p = frame.to_global(Enu::new(east, north));
+        Measurement::new(p.lat, p.lon, distance)
+    }
+
+    #[test]
+    fn test_select_by_count_and_geometry() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let corridor: Vec<_> = (0..4)
+            .map(|i| measurement(&frame, 5.0 * i as f64, 0.0, 8.0))
+            .collect();
+        let room = vec![
+            measurement(&frame, 0.0, 0.0, 3.0),
+            measurement(&frame, 8.0, 0.0, 3.0),
+            measurement(&frame, 0.0, 6.0, 3.0),
+        ];
+
+        assert_eq!(select(&corridor[..1]), Method::NearestBeacon);
+        assert_eq!(select(&corridor[..2]), Method::MinMax);
+        assert_eq!(select(&corridor), Method::MinMax);
+        assert_eq!(select(&room), Method::Trilateration);
+    }
+
+    #[test]
+    fn test_select_centroid_when_ranges_do_not_intersect() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let apart = vec![
+            measurement(&frame, 0.0, 0.0, 2.0),
+            measurement(&frame, 10.0, 0.0, 3.0),
+        ];
+        let inside = vec![
+            measurement(&frame, 0.0, 0.0, 12.0),
+            measurement(&frame, 4.0, 0.0, 2.0),
+        ];
+        let corridor: Vec<_> = (0..3)
+            .map(|i| measurement(&frame, 5.0 * i as f64, 0.0, 1.0))
+            .collect();
+
+        assert_eq!(select(&apart), Method::WeightedCentroid);
+        assert_eq!(select(&inside), Method::WeightedCentroid);
+        assert_eq!(select(&corridor), Method::WeightedCentroid);
+        assert_eq!(centroid(&apart).unwrap().method, Method::WeightedCentroid);
+    }
+
+    #[test]
+    fn test_min_max_between_two_beacons() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let measurements = vec![
+            measurement(&frame, 0.0, 0.0, 4.0),
+            measurement(&frame, 6.0, 0.0, 3.0),
+        ];
+
+        let estimate = min_max(&measurements).unwrap();
+        let expected = frame.to_global(Enu::new(3.5, 0.0));
+        assert!(haversine_distance(estimate.position, expected) < 0.01);
+        assert!((estimate.radius - 0.5f64.hypot(3.0)).abs() < 0.01);
+        assert_eq!(estimate.method, Method::MinMax);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::beacon::BeaconId;
+use crate::offline::calibration::{Calibration, CalibrationTable, Scope};
+use crate::offline::locator::resolve;
+use crate::signal::Signal;
+use anyhow::bail;
+use log::warn;
+use std::collections::HashMap;
+
+/// A received signal recorded at a known distance in metres from its beacon.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub signal: Signal<BeaconId>,
+    pub distance: f64,
+}
+
+impl Sample {
+    pub fn new(signal: Signal<BeaconId>, distance: f64) -> Self {
+        Self { signal, distance }
+    }
+}
+
+/// Which samples share a fitted calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    Beacon,
+    Building,
+}
+
+/// Log-distance parameters fitted to a set of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    /// RSSI at one metre.
+    pub tx_power: f64,
+    pub exponent: f64,
+    /// Root mean square of the RSSI residuals in dB.
+    pub rms: f64,
+    pub samples: usize,
+}
+
+impl From<Fit> for Calibration {
+    fn from(fit: Fit) -> Self {
+        Calibration {
+            tx_power: Some(fit.tx_power.round() as i8),
+            exponent: Some(fit.exponent),
+            rssi_offset: None,
+            height: None,
+        }
+    }
+}
+
+/// Fits `rssi = tx_power - 10 * exponent * log10(distance)` with ordinary least squares.
+///
+/// Needs samples from at least two different distances, and fails unless the RSSI
+/// falls with distance.
+pub fn fit_log_distance(samples: &[(i8, f64)]) -> anyhow::Result<Fit> {
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .filter(|(_, d)| *d > 0.0)
+        .map(|(rssi, d)| (-10.0 * d.log10(), *rssi as f64))
+        .collect();
+    if points.len() < 2 {
+        bail!("need at least two samples, got {}", points.len());
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    if sxx < f64::EPSILON {
+        bail!("all samples were recorded at the same distance");
+    }
+
+    let exponent = sxy / sxx;
+    if !exponent.is_finite() || exponent <= 0.0 {
+        bail!("RSSI does not fall with distance, exponent {}", exponent);
+    }
+    let tx_power = mean_y - exponent * mean_x;
+    let rms = (points
+        .iter()
+        .map(|(x, y)| (y - tx_power - exponent * x).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+
+    Ok(Fit {
+        tx_power,
+        exponent,
+        rms,
+        samples: points.len(),
+    })
+}
+
+/// Fits one calibration per beacon or building. Groups that cannot be fitted are
+/// skipped with a warning.
+pub fn fit_calibration(samples: &[Sample], grouping: Grouping) -> CalibrationTable {
+    let mut groups: HashMap<Scope, Vec<(i8, f64)>> = HashMap::new();
+    for sample in samples {
+        let scope = match grouping {
+            Grouping::Beacon => Scope::Beacon(sample.signal.beacon.clone()),
+            Grouping::Building => match resolve(&sample.signal.beacon) {
+                Some(beacon) => Scope::Building(beacon.location.building),
+                None => continue,
+            },
+        };
+        groups
+            .entry(scope)
+            .or_default()
+            .push((sample.signal.rssi, sample.distance));
+    }
+
+    let mut table = CalibrationTable::default();
+    for (scope, points) in groups {
+        match fit_log_distance(&points) {
+            Ok(fit) => table.insert(scope, fit.into()),
+            Err(e) => warn!("cannot fit {}: {}", scope, e),
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_recovers_log_distance_parameters() {
+        let samples: Vec<(i8, f64)> = [1.0, 2.0, 4.0, 8.0, 16.0]
+            .iter()
+            .map(|d: &f64| ((-61.0 - 25.0 * d.log10()).round() as i8, *d))
+            .collect();
+
+        let fit = fit_log_distance(&samples).unwrap();
+        assert!((fit.tx_power + 61.0).abs() < 0.5, "{:?}", fit);
+        assert!((fit.exponent - 2.5).abs() < 0.1, "{:?}", fit);
+        assert!(fit_log_distance(&[(-70, 2.0), (-71, 2.0)]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_rssi_not_falling_with_distance() {
+        assert!(fit_log_distance(&[(-70, 1.0), (-70, 2.0), (-70, 4.0)]).is_err());
+        assert!(fit_log_distance(&[(-75, 1.0), (-70, 4.0)]).is_err());
+
+        let clock = ManualClock::default();
+        let id = BeaconId::new("uuid", 1, 2);
+        let samples: Vec<_> = [(-80, 1.0), (-70, 4.0)]
+            .iter()
+            .map(|(rssi, d)| Sample::new(Signal::new(id.clone(), -70, *rssi, &clock), *d))
+            .collect();
+        assert!(fit_calibration(&samples, Grouping::Beacon).is_empty());
+    }
+
+    #[test]
+    fn test_fits_per_beacon() {
+        let clock = ManualClock::default();
+        let id = BeaconId::new("uuid", 1, 2);
+        let samples: Vec<_> = [(-59, 1.0), (-80, 4.0), (-69, 2.0)]
+            .iter()
+            .map(|(rssi, d)| Sample::new(Signal::new(id.clone(), -70, *rssi, &clock), *d))
+            .collect();
+
+        let table = fit_calibration(&samples, Grouping::Beacon);
+        let calibration = table.lookup(&id, &Default::default());
+        assert_eq!(calibration.tx_power, Some(-59));
+    }
+}
@@ -0,0 +1,56 @@
+/// What to do with a trilateration whose beacon geometry is poor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryPolicy {
+    /// Publish no position at all.
+    Reject,
+    /// Publish the position but mark it as poor.
+    Flag,
+    /// Publish the room with the position of the nearest beacon.
+    RoomOnly,
+}
+
+/// Gates trilaterated fixes on their dilution of precision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometryGate {
+    max_dop: f64,
+    policy: GeometryPolicy,
+}
+
+impl Default for GeometryGate {
+    fn default() -> Self {
+        Self {
+            max_dop: 5.0,
+            policy: GeometryPolicy::Flag,
+        }
+    }
+}
+
+impl GeometryGate {
+    pub fn new(max_dop: f64, policy: GeometryPolicy) -> Self {
+        Self { max_dop, policy }
+    }
+
+    /// The policy to apply to a fix, `None` if its geometry is good enough.
+    ///
+    /// An unknown dilution means an unconstrained axis and always counts as poor.
+    pub fn check(&self, dop: Option<f64>) -> Option<GeometryPolicy> {
+        match dop {
+            Some(dop) if dop <= self.max_dop => None,
+            _ => Some(self.policy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let gate = GeometryGate::new(4.0, GeometryPolicy::RoomOnly);
+
+        assert_eq!(gate.check(Some(1.2)), None);
+        assert_eq!(gate.check(Some(6.0)), Some(GeometryPolicy::RoomOnly));
+        assert_eq!(gate.check(None), Some(GeometryPolicy::RoomOnly));
+    }
+}
@@ -1,90 +1,403 @@
-use crate::beacon::{Beacon, BeaconId, Output, Room};
-use crate::geographic::Position;
-use crate::offline::trilateration::trilaterate;
-use crate::signal::Signal;
-use log::{error, info};
+use crate::beacon::{Beacon, BeaconId, Method, Output, Room, Source};
+use crate::geographic::{Position, haversine_distance};
+use crate::level::Level;
+use crate::offline::calibration::{CalibrationTable, SharedCalibration};
+use crate::offline::fallback;
+use crate::offline::geometry::{GeometryGate, GeometryPolicy};
+use crate::offline::particle::{Observation, ParticleFilter};
+use crate::offline::room::{RoomResolver, same_floor};
+use crate::offline::signal::{LogDistance, PathLossModel, SHADOWING_VARIANCE};
+use crate::offline::trilateration::{
+    Measurement, NelderMead, Solver, TrackerHeight, trilaterate, weighted_centroid,
+};
+use crate::signal::{Batch, Motion, Signal};
+use anyhow::bail;
+use log::{error, info, warn};
+use std::collections::HashMap;
 
-#[derive(Default)]
-pub struct Locator {}
+/// Distance in metres between the previous fix and the beacons up to which the previous
+/// fix seeds the solver.
+const MAX_SEED_DISTANCE: f64 = 30.0;
+/// Mounting height in metres above the floor of beacons without a calibrated one, i.e.
+/// on the ceiling.
+const DEFAULT_MOUNTING_HEIGHT: f64 = 2.5;
+
+pub struct Locator {
+    model: Box<dyn PathLossModel>,
+    /// Overrides of the model for individual buildings.
+    buildings: HashMap<String, Box<dyn PathLossModel>>,
+    calibration: SharedCalibration,
+    solver: Box<dyn Solver>,
+    geometry: GeometryGate,
+    rooms: RoomResolver,
+    height: TrackerHeight,
+    tracker: Option<ParticleFilter>,
+    previous: Option<Position>,
+}
+
+/// The path-loss model applying to a beacon.
+enum BeaconModel<'a> {
+    Calibrated(LogDistance),
+    Configured(&'a dyn PathLossModel),
+}
+
+impl BeaconModel<'_> {
+    fn get(&self) -> &dyn PathLossModel {
+        match self {
+            BeaconModel::Calibrated(model) => model,
+            BeaconModel::Configured(model) => *model,
+        }
+    }
+}
+
+impl Default for Locator {
+    fn default() -> Self {
+        Self::new(LogDistance::default())
+    }
+}
 
 impl Locator {
-    pub(crate) fn locate(&self, signals: Vec<Signal<BeaconId>>) -> anyhow::Result<Output> {
-        let resolved_signals = Self::resolve_beacons(signals);
-        let distances_signals = Self::calculate_signal_distance(resolved_signals);
+    pub fn new(model: impl PathLossModel + 'static) -> Self {
+        Self {
+            model: Box::new(model),
+            buildings: HashMap::new(),
+            calibration: SharedCalibration::default(),
+            solver: Box::new(NelderMead::default()),
+            geometry: GeometryGate::default(),
+            rooms: RoomResolver::default(),
+            height: TrackerHeight::default(),
+            tracker: None,
+            previous: None,
+        }
+    }
+
+    pub fn with_calibration(mut self, calibration: SharedCalibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn with_building_model(
+        mut self,
+        building: &str,
+        model: impl PathLossModel + 'static,
+    ) -> Self {
+        self.buildings.insert(building.to_string(), Box::new(model));
+        self
+    }
 
-        if let Some(first) = distances_signals.first() {
+    pub fn with_solver(mut self, solver: impl Solver + 'static) -> Self {
+        self.solver = Box::new(solver);
+        self
+    }
+
+    pub fn with_geometry(mut self, geometry: GeometryGate) -> Self {
+        self.geometry = geometry;
+        self
+    }
+
+    pub fn with_room_resolver(mut self, rooms: RoomResolver) -> Self {
+        self.rooms = rooms;
+        self
+    }
+
+    pub fn with_tracker_height(mut self, height: TrackerHeight) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_particle_filter(mut self, filter: ParticleFilter) -> Self {
+        self.tracker = Some(filter);
+        self
+    }
+
+    /// Calibrated exponents take precedence over the configured models.
+    fn model<'a>(&'a self, calibration: &CalibrationTable, beacon: &Beacon) -> BeaconModel<'a> {
+        match calibration.lookup(&beacon.id, &beacon.location).exponent {
+            Some(exponent) => BeaconModel::Calibrated(LogDistance::new(exponent)),
+            None => BeaconModel::Configured(
+                self.buildings
+                    .get(&beacon.location.building)
+                    .map_or(self.model.as_ref(), |m| m.as_ref()),
+            ),
+        }
+    }
+
+    pub(crate) fn locate(&mut self, batch: Batch<BeaconId>) -> anyhow::Result<Output> {
+        info!("locating with motion state {:?}", batch.motion);
+        let motion = batch.motion;
+
+        let resolved_signals = self.resolve_beacons(batch.signals);
+        let distances_signals = self.calculate_signal_distance(resolved_signals);
+
+        if let Some(room) = self.rooms.resolve(distances_signals.iter().map(|(s, _)| s)) {
+            // beacons on other floors are heard through the ceiling, their ranges are off
+            let distances_signals: Vec<_> = distances_signals
+                .into_iter()
+                .filter(|(s, _)| same_floor(&s.beacon.location, &room))
+                .collect();
             distances_signals
                 .iter()
                 .for_each(|x| info!("calculated distance to beacon {:?}", x));
 
-            let measurements: Vec<_> = distances_signals
+            let (ids, measurements): (Vec<_>, Vec<_>) = distances_signals
                 .iter()
-                .filter_map(|s| {
+                .filter_map(|(s, variance)| {
                     s.distance.map(|d| {
-                        super::trilateration::Measurement::new(
-                            s.beacon.position.lat,
-                            s.beacon.position.lon,
-                            d,
-                        )
+                        let position = s.beacon.position;
+                        let measurement = Measurement::new(position.lat, position.lon, d)
+                            .with_variance(*variance);
+                        let measurement = match position.alt {
+                            Some(alt) => measurement.with_altitude(alt),
+                            None => measurement,
+                        };
+                        (s.beacon.id.clone(), measurement)
                     })
                 })
-                .collect();
+                .unzip();
+
+            let beacons = measurements.len();
+            // resolved beacons, and so their rooms, always have a floor
+            let floor = room.height().unwrap_or_default();
+            let timestamp = distances_signals.iter().map(|(s, _)| s.rx_ts).max();
+
+            let mut output = match fallback::select(&measurements) {
+                Method::Trilateration => {
+                    let nearest = fallback::nearest_beacon(&measurements);
+                    let start = Self::start(&measurements, self.previous);
+                    let fix = trilaterate(
+                        self.solver.as_ref(),
+                        measurements,
+                        start,
+                        self.height,
+                        floor,
+                    )?;
+
+                    let output = match (self.geometry.check(fix.dop), nearest) {
+                        (Some(GeometryPolicy::Reject), _) => {
+                            bail!("beacon geometry too poor, DOP {:?}", fix.dop)
+                        }
+                        (Some(GeometryPolicy::RoomOnly), Some(nearest)) => {
+                            info!("poor geometry, DOP {:?}, publishing room only", fix.dop);
+                            Output::new(nearest.position, room, None, None)
+                                .with_method(Method::RoomOnly)
+                                .with_source(Source::Fallback)
+                                .with_accuracy(nearest.radius)
+                        }
+                        (policy, _) => {
+                            let output = Output::new(fix.position, room, None, None)
+                                .with_method(Method::Trilateration)
+                                .with_source(Source::Offline)
+                                .with_poor_geometry(policy.is_some());
+                            match fix.accuracy {
+                                Some(accuracy) => output.with_accuracy(accuracy),
+                                None => output,
+                            }
+                        }
+                    }
+                    .with_cost(fix.cost)
+                    .with_rejected(fix.outliers.iter().map(|&i| ids[i].clone()).collect());
+                    if !output.rejected.is_empty() {
+                        info!("rejected inconsistent beacons {:?}", output.rejected);
+                    }
+                    match fix.dop {
+                        Some(dop) => output.with_dop(dop),
+                        None => output,
+                    }
+                }
+                method => {
+                    let estimate = match method {
+                        Method::MinMax => fallback::min_max(&measurements),
+                        Method::WeightedCentroid => fallback::centroid(&measurements),
+                        _ => fallback::nearest_beacon(&measurements),
+                    }
+                    .ok_or_else(|| anyhow::anyhow!("no distances to locate with"))?;
+                    info!("{:?} fix within {:.1} m", estimate.method, estimate.radius);
+                    Output::new(estimate.position, room, None, None)
+                        .with_method(estimate.method)
+                        .with_source(Source::Fallback)
+                        .with_accuracy(estimate.radius)
+                }
+            };
+            if self.tracker.is_some() {
+                // a fix without an estimated height is at the tracker's assumed one
+                let height = output
+                    .position
+                    .alt
+                    .unwrap_or(floor + self.height.above_floor());
+                output = self.track(&distances_signals, motion, height, output);
+            }
+            let used = beacons - output.rejected.len();
+            output = output.with_beacons(used);
+            if let Some(timestamp) = timestamp {
+                output = output.with_timestamp(timestamp);
+            }
+            self.previous = Some(output.position);
 
-            // Perform trilateration and return result
-            Ok(Output::new(
-                trilaterate(measurements)?,
-                first.beacon.location.clone(),
-                None,
-                None,
-            ))
+            Ok(output)
         } else {
             Err(anyhow::anyhow!("did not find any signals"))
         }
     }
 
-    fn resolve_beacons(signals: Vec<Signal<BeaconId>>) -> Vec<Signal<Beacon>> {
-        signals
-            .iter()
-            .flat_map(|s| {
-                let resolved_beacon = eth_beacons_indoor::resolver::find_beacon_by_id(
-                    s.beacon.uuid.as_str(),
-                    s.beacon.major,
-                    s.beacon.minor,
-                );
+    /// Replaces the snapshot fix by the particle filter estimate.
+    ///
+    /// Beacons the snapshot rejected as inconsistent are left out of the update too. The
+    /// source is kept, so a fix refined from a fallback still shows as degraded.
+    fn track(
+        &mut self,
+        signals: &[(Signal<Beacon>, f64)],
+        motion: Motion,
+        height: f64,
+        output: Output,
+    ) -> Output {
+        let Some(mut filter) = self.tracker.take() else {
+            return output;
+        };
+        let calibration = self.calibration.read().unwrap_or_else(|e| e.into_inner());
 
-                if resolved_beacon.is_none() {
-                    error!(
-                        "beacon for uuid {}, major {}, minor {} not found",
-                        s.beacon.uuid.as_str(),
-                        s.beacon.major,
-                        s.beacon.minor
-                    );
+        let observations: Vec<Observation> = signals
+            .iter()
+            .filter(|(s, _)| !output.rejected.contains(&s.beacon.id))
+            .map(|(s, _)| {
+                let model = self.model(&calibration, &s.beacon);
+                let tx_power = s.tx_power;
+                Observation {
+                    position: s.beacon.position,
+                    rssi: s.rssi as f64,
+                    variance: SHADOWING_VARIANCE + s.variance.unwrap_or_default(),
+                    expected: Box::new(move |d| model.get().rssi(d, tx_power)),
                 }
+            })
+            .collect();
+        let now = signals
+            .iter()
+            .map(|(s, _)| s.rx_monotonic)
+            .max()
+            .unwrap_or_default();
+        let accuracy = output.accuracy.unwrap_or(5.0);
+
+        let (position, spread) = filter.track(
+            &observations,
+            motion,
+            now,
+            height,
+            output.position,
+            accuracy,
+        );
+        info!("particle filter fix within {:.1} m", spread);
+        drop(observations);
+        drop(calibration);
+        self.tracker = Some(filter);
+
+        Output {
+            position: Position {
+                alt: output.position.alt,
+                ..position
+            },
+            ..output
+        }
+        .with_accuracy(spread)
+        .with_method(Method::ParticleFilter)
+    }
+
+    /// Seeds the solver with the previous fix unless the beacons heard now are far
+    /// from it, e.g. after changing buildings.
+    fn start(measurements: &[Measurement], previous: Option<Position>) -> Option<Position> {
+        let centroid = weighted_centroid(measurements)?;
+        match previous {
+            Some(p) if haversine_distance(p, centroid) < MAX_SEED_DISTANCE => Some(p),
+            _ => Some(centroid),
+        }
+    }
 
-                resolved_beacon.map(|b| {
-                    let id = BeaconId::new(b.id.uuid, b.id.major, b.id.minor);
-                    let loc = &b.location;
-                    let location = Room::new(loc.building.as_ref(), loc.floor, loc.room);
-                    let position = Position::new(b.position.lat, b.position.lon);
+    fn resolve_beacons(&self, signals: Vec<Signal<BeaconId>>) -> Vec<Signal<Beacon>> {
+        let calibration = self.calibration.read().unwrap_or_else(|e| e.into_inner());
 
-                    Signal::new(Beacon::new(id, location, position), s.tx_power, s.rssi)
+        signals
+            .iter()
+            .flat_map(|s| {
+                resolve(&s.beacon).map(|beacon| {
+                    let c = calibration.lookup(&beacon.id, &beacon.location);
+                    let mut signal = s.clone().map_beacon(|_| beacon);
+                    if let Some(tx_power) = c.tx_power {
+                        signal.tx_power = tx_power;
+                    }
+                    if let Some(offset) = c.rssi_offset {
+                        signal.rssi = signal.rssi.saturating_add(offset);
+                    }
+                    if let Some(floor) = signal.beacon.location.height() {
+                        let mounting = c.height.unwrap_or(DEFAULT_MOUNTING_HEIGHT);
+                        signal.beacon.position =
+                            signal.beacon.position.with_altitude(floor + mounting);
+                    }
+                    signal
                 })
             })
             .collect()
     }
 
-    fn calculate_signal_distance(signals: Vec<Signal<Beacon>>) -> Vec<Signal<Beacon>> {
+    /// Pairs each signal with the variance of its distance, nearest first.
+    ///
+    /// Signals whose model yields no finite distance are left out.
+    fn calculate_signal_distance(
+        &self,
+        signals: Vec<Signal<Beacon>>,
+    ) -> Vec<(Signal<Beacon>, f64)> {
+        let calibration = self.calibration.read().unwrap_or_else(|e| e.into_inner());
+
         let mut result = signals
             .iter()
-            .map(move |s| {
-                let distance = super::signal::calculate_distance(s.rssi, s.tx_power);
-                s.clone().with_distance(distance) // Clone `s` before modifying it
+            .filter_map(|s| {
+                let rssi_variance = SHADOWING_VARIANCE + s.variance.unwrap_or_default();
+                let estimate = self.model(&calibration, &s.beacon).get().estimate(
+                    s.rssi,
+                    s.tx_power,
+                    rssi_variance,
+                );
+                if !estimate.distance.is_finite() || !estimate.variance.is_finite() {
+                    warn!("no distance to beacon {:?} at {} dBm", s.beacon.id, s.rssi);
+                    return None;
+                }
+                Some((
+                    s.clone().with_distance(estimate.distance),
+                    estimate.variance,
+                ))
             })
-            .filter(|d| d.distance.is_some())
-            .collect::<Vec<Signal<Beacon>>>();
+            .collect::<Vec<_>>();
 
-        result.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        result.sort_by(|(a, _), (b, _)| {
+            a.distance
+                .unwrap_or_default()
+                .total_cmp(&b.distance.unwrap_or_default())
+        });
 
         result
     }
 }
+
+/// Looks up the position and room of a beacon in the ETH beacon database.
+pub(crate) fn resolve(id: &BeaconId) -> Option<Beacon> {
+    let resolved_beacon =
+        eth_beacons_indoor::resolver::find_beacon_by_id(id.uuid.as_str(), id.major, id.minor);
+
+    if resolved_beacon.is_none() {
+        error!(
+            "beacon for uuid {}, major {}, minor {} not found",
+            id.uuid.as_str(),
+            id.major,
+            id.minor
+        );
+    }
+
+    resolved_beacon.and_then(|b| {
+        let id = BeaconId::new(b.id.uuid, b.id.major, b.id.minor);
+        let loc = &b.location;
+        let floor = Level::parse(loc.building.as_ref(), loc.floor)
+            .inspect_err(|e| error!("beacon {:?} has {}", id, e))
+            .ok()?;
+        let location = Room::new(loc.building.as_ref(), floor, loc.room);
+        let position = Position::new(b.position.lat, b.position.lon);
+
+        Some(Beacon::new(id, location, position))
+    })
+}
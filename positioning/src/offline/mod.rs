@@ -1,49 +1,124 @@
+mod calibration;
+mod fallback;
+mod fit;
+mod geometry;
 mod locator;
+mod particle;
+mod random;
+mod room;
 mod signal;
+mod tracking;
 mod trilateration;
 
 use crate::beacon::{BeaconId, Output};
-use crate::signal::Signal;
-use crossbeam_channel::{Receiver, Sender, select};
+use crate::pipeline::{BoundedSender, Stage};
+use crate::signal::Batch;
+use crossbeam_channel::{Receiver, select};
 use log::error;
-use std::thread;
-use std::thread::JoinHandle;
 
+pub use calibration::{Calibration, CalibrationTable, Scope, SharedCalibration};
+pub use fit::{Fit, Grouping, Sample, fit_calibration, fit_log_distance};
+pub use geometry::{GeometryGate, GeometryPolicy};
+pub use particle::ParticleFilter;
+pub use room::RoomResolver;
+pub use signal::{DistanceEstimate, ItuIndoor, LogDistance, PathLossModel, Polynomial};
+pub use tracking::PositionFilter;
+pub use trilateration::{
+    GaussNewton, LevenbergMarquardt, LinearLeastSquares, NelderMead, Range, Ransac, Solution,
+    Solver, TrackerHeight,
+};
+
+/// Locates the tracker on the device from the resolved beacon positions.
 #[derive(Default)]
-pub struct Locator {}
+pub struct Locator {
+    locator: locator::Locator,
+}
 
 impl Locator {
+    pub fn new(model: impl PathLossModel + 'static) -> Self {
+        Self {
+            locator: locator::Locator::new(model),
+        }
+    }
+
+    /// Uses a different model for beacons in the given building, e.g. `"HG"`.
+    pub fn with_building_model(self, building: &str, model: impl PathLossModel + 'static) -> Self {
+        Self {
+            locator: self.locator.with_building_model(building, model),
+        }
+    }
+
+    /// Applies the calibration when resolving beacons; the table may be replaced while
+    /// the locator runs.
+    pub fn with_calibration(self, calibration: SharedCalibration) -> Self {
+        Self {
+            locator: self.locator.with_calibration(calibration),
+        }
+    }
+
+    /// Replaces the default Nelder-Mead trilateration solver, e.g. with a [`Ransac`]
+    /// wrapper to leave out beacons inconsistent with the others.
+    pub fn with_solver(self, solver: impl Solver + 'static) -> Self {
+        Self {
+            locator: self.locator.with_solver(solver),
+        }
+    }
+
+    /// Decides what to publish when the beacons are poorly spread around the fix.
+    pub fn with_geometry(self, geometry: GeometryGate) -> Self {
+        Self {
+            locator: self.locator.with_geometry(geometry),
+        }
+    }
+
+    /// Fixes the tracker at a known height above the floor or estimates it; beacon
+    /// heights come from the calibration, by default they hang from the ceiling.
+    pub fn with_tracker_height(self, height: TrackerHeight) -> Self {
+        Self {
+            locator: self.locator.with_tracker_height(height),
+        }
+    }
+
+    /// Replaces the default voting parameters used to determine the room.
+    pub fn with_room_resolver(self, rooms: RoomResolver) -> Self {
+        Self {
+            locator: self.locator.with_room_resolver(rooms),
+        }
+    }
+
+    /// Tracks the position across batches instead of locating each batch on its own.
+    pub fn with_particle_filter(self, filter: ParticleFilter) -> Self {
+        Self {
+            locator: self.locator.with_particle_filter(filter),
+        }
+    }
+
     pub fn start(
         self,
-        rx: Receiver<Vec<Signal<BeaconId>>>,
-        tx: Sender<Output>,
-    ) -> anyhow::Result<JoinHandle<()>> {
-        let handle = thread::Builder::new()
-            .name("locator".to_string())
-            .stack_size(8 * 1024) // 8 KB stack
-            .spawn(move || {
-                let positioning = locator::Locator::default();
-
-                loop {
-                    select! {
-                        recv(rx) -> msg => match msg {
-                            Ok(m) => {
-                             match positioning.locate(m) {
-                                Ok(output) => {
-                                    if let  Err(e) = tx.send(output){
-                                        error!("Failed to send position to service client: {}", e);
-                                    }
-                                },
-                                Err(e) => error!("Failed to publish measurement: {}", e),
-                            }
-                            }
-                            Err(_) => break,
+        rx: Receiver<Batch<BeaconId>>,
+        tx: BoundedSender<Output>,
+    ) -> anyhow::Result<Stage> {
+        Stage::spawn("locator", 8 * 1024, move |stop| {
+            let mut positioning = self.locator;
+
+            loop {
+                select! {
+                    recv(rx) -> msg => match msg {
+                        Ok(m) => {
+                         match positioning.locate(m) {
+                            Ok(output) => {
+                                if let  Err(e) = tx.send(output){
+                                    error!("Failed to send position to service client: {}", e);
+                                }
+                            },
+                            Err(e) => error!("Failed to publish measurement: {}", e),
                         }
-                    }
+                        }
+                        Err(_) => break,
+                    },
+                    recv(stop) -> _ => break,
                 }
-            })
-            .expect("cannot spawn locator thread");
-
-        Ok(handle)
+            }
+        })
     }
 }
@@ -0,0 +1,256 @@
+use crate::geographic::{Enu, LocalFrame, Position};
+use crate::offline::random::XorShift;
+use crate::signal::Motion;
+use std::time::Duration;
+
+/// Distance in metres from the particle cloud beyond which a snapshot fix restarts it.
+const REINIT_DISTANCE: f64 = 30.0;
+/// Longest prediction step, so a long gap does not scatter the particles over campus.
+const MAX_STEP: Duration = Duration::from_secs(10);
+
+/// A beacon heard in a batch, with the RSSI expected at a given distance from it.
+pub(crate) struct Observation<'a> {
+    /// Position of the beacon, its altitude if known.
+    pub position: Position,
+    pub rssi: f64,
+    /// RSSI variance in dB².
+    pub variance: f64,
+    pub expected: Box<dyn Fn(f64) -> f64 + 'a>,
+}
+
+/// Tracks the position across batches with a particle filter.
+///
+/// Particles follow a pedestrian random walk whose spread depends on the detected
+/// motion and are weighted by how well the RSSI expected from the path-loss model
+/// matches the received one. The estimate is the weighted mean, i.e. the minimum mean
+/// square error estimate, and its spread.
+#[derive(Debug, Clone)]
+pub struct ParticleFilter {
+    count: usize,
+    /// Speed in m/s of the random walk while moving.
+    walking_speed: f64,
+    /// Speed in m/s of the random walk while stationary, absorbing RSSI drift.
+    stationary_speed: f64,
+    particles: Vec<Enu>,
+    weights: Vec<f64>,
+    frame: Option<LocalFrame>,
+    updated: Option<Duration>,
+    rng: XorShift,
+}
+
+impl Default for ParticleFilter {
+    fn default() -> Self {
+        Self::new(300)
+    }
+}
+
+impl ParticleFilter {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: count.max(1),
+            walking_speed: 1.4,
+            stationary_speed: 0.1,
+            particles: Vec::new(),
+            weights: Vec::new(),
+            frame: None,
+            updated: None,
+            rng: XorShift::new(0x2545_f491_4f6c_dd1d),
+        }
+    }
+
+    pub fn walking_speed(mut self, walking_speed: f64) -> Self {
+        self.walking_speed = walking_speed;
+        self
+    }
+
+    /// Advances the filter to `now` and returns the position and its spread in metres.
+    ///
+    /// `snapshot` is the independent fix of the same batch with its accuracy; the
+    /// particles are spread around it on the first call and whenever they lose track.
+    /// Distances to beacons of known altitude are slant ranges from `height`, the
+    /// altitude of the tracker.
+    pub(crate) fn track(
+        &mut self,
+        observations: &[Observation],
+        motion: Motion,
+        now: Duration,
+        height: f64,
+        snapshot: Position,
+        accuracy: f64,
+    ) -> (Position, f64) {
+        let lost = match self.frame {
+            Some(frame) => {
+                let (mean, _) = self.estimate();
+                frame.to_local(snapshot).distance(mean) > REINIT_DISTANCE
+            }
+            None => true,
+        };
+        if lost {
+            self.reset(snapshot, accuracy.max(1.0));
+        } else {
+            self.predict(motion, now);
+        }
+        self.updated = Some(now);
+
+        if !self.update(observations, height) {
+            self.reset(snapshot, accuracy.max(1.0));
+        }
+
+        let frame = self.frame.expect("filter is initialised");
+        let (mean, spread) = self.estimate();
+        (frame.to_global(mean), spread)
+    }
+
+    fn reset(&mut self, center: Position, spread: f64) {
+        let rng = &mut self.rng;
+        self.particles = (0..self.count)
+            .map(|_| Enu::new(spread * rng.gaussian(), spread * rng.gaussian()))
+            .collect();
+        self.weights = vec![1.0 / self.count as f64; self.count];
+        self.frame = Some(LocalFrame::new(center));
+    }
+
+    fn predict(&mut self, motion: Motion, now: Duration) {
+        let step = self
+            .updated
+            .and_then(|t| now.checked_sub(t))
+            .unwrap_or_default()
+            .min(MAX_STEP);
+        let speed = match motion {
+            Motion::Moving => self.walking_speed,
+            Motion::Stationary => self.stationary_speed,
+            Motion::Unknown => self.walking_speed / 2.0,
+        };
+
+        let sigma = (speed * step.as_secs_f64()).max(0.05);
+        for p in &mut self.particles {
+            p.east += sigma * self.rng.gaussian();
+            p.north += sigma * self.rng.gaussian();
+        }
+    }
+
+    /// Reweighs the particles; `false` if no particle explains the observations.
+    fn update(&mut self, observations: &[Observation], height: f64) -> bool {
+        let frame = self.frame.expect("filter is initialised");
+        let beacons: Vec<(Enu, f64)> = observations
+            .iter()
+            .map(|o| {
+                let up = o.position.alt.map_or(0.0, |alt| alt - height);
+                (frame.to_local(o.position), up)
+            })
+            .collect();
+
+        let log_weights: Vec<f64> = self
+            .particles
+            .iter()
+            .zip(&self.weights)
+            .map(|(p, w)| {
+                let likelihood: f64 = observations
+                    .iter()
+                    .zip(&beacons)
+                    .map(|(o, (b, up))| {
+                        let expected = (o.expected)(p.distance(*b).hypot(*up).max(0.1));
+                        -(o.rssi - expected).powi(2) / (2.0 * o.variance)
+                    })
+                    .sum();
+                w.ln() + likelihood
+            })
+            .collect();
+
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if !max.is_finite() {
+            return false;
+        }
+        let weights: Vec<f64> = log_weights.iter().map(|l| (l - max).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        self.weights = weights.iter().map(|w| w / total).collect();
+
+        let effective = 1.0 / self.weights.iter().map(|w| w * w).sum::<f64>();
+        if effective < self.count as f64 / 2.0 {
+            self.resample();
+        }
+        true
+    }
+
+    /// Systematic resampling.
+    fn resample(&mut self) {
+        let n = self.count;
+        let offset = self.rng.uniform() / n as f64;
+        let mut resampled = Vec::with_capacity(n);
+        let mut cumulative = self.weights[0];
+        let mut i = 0;
+        for k in 0..n {
+            let target = offset + k as f64 / n as f64;
+            while cumulative < target && i + 1 < n {
+                i += 1;
+                cumulative += self.weights[i];
+            }
+            resampled.push(self.particles[i]);
+        }
+        self.particles = resampled;
+        self.weights = vec![1.0 / n as f64; n];
+    }
+
+    /// Weighted mean of the particles and the root of their summed variances.
+    fn estimate(&self) -> (Enu, f64) {
+        let (mut east, mut north) = (0.0, 0.0);
+        for (p, w) in self.particles.iter().zip(&self.weights) {
+            east += w * p.east;
+            north += w * p.north;
+        }
+        let mean = Enu::new(east, north);
+        let variance: f64 = self
+            .particles
+            .iter()
+            .zip(&self.weights)
+            .map(|(p, w)| w * p.distance(mean).powi(2))
+            .sum();
+        (mean, variance.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geographic::haversine_distance;
+    use crate::offline::signal::{LogDistance, PathLossModel};
+
+    #[test]
+    fn test_converges_on_stationary_tracker() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let target = Enu::new(4.0, 3.0);
+        let model = LogDistance::default();
+        let beacons = [
+            Enu::new(0.0, 0.0),
+            Enu::new(10.0, 0.0),
+            Enu::new(10.0, 8.0),
+            Enu::new(0.0, 8.0),
+        ];
+
+        let mut filter = ParticleFilter::new(500);
+        let snapshot = frame.to_global(Enu::new(6.0, 5.0));
+        let mut result = (snapshot, f64::MAX);
+        for step in 0..10 {
+            let observations: Vec<_> = beacons
+                .iter()
+                // on the ceiling, 1.7 m above the tracker
+                .map(|b| Observation {
+                    position: frame.to_global(*b).with_altitude(2.7),
+                    rssi: model.rssi(target.distance(*b).hypot(1.7), -59),
+                    variance: 16.0,
+                    expected: Box::new(|d| model.rssi(d, -59)),
+                })
+                .collect();
+            let now = Duration::from_secs(5 * step);
+            result = filter.track(&observations, Motion::Stationary, now, 1.0, snapshot, 3.0);
+        }
+
+        let (position, spread) = result;
+        let error = haversine_distance(position, frame.to_global(target));
+        assert!(error < 0.5, "error {}", error);
+        assert!(spread < 1.5, "spread {}", spread);
+    }
+}
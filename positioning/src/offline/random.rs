@@ -0,0 +1,41 @@
+use std::f64::consts::TAU;
+
+/// Small deterministic generator, good enough for sampling but not for cryptography.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, by the Box-Muller transform.
+    pub fn gaussian(&mut self) -> f64 {
+        let u = 1.0 - self.uniform();
+        (-2.0 * u.ln()).sqrt() * (TAU * self.uniform()).cos()
+    }
+
+    /// `k` distinct indices below `n`.
+    pub fn sample(&mut self, n: usize, k: usize) -> Vec<usize> {
+        let mut picked = Vec::with_capacity(k);
+        while picked.len() < k.min(n) {
+            let i = (self.next() % n as u64) as usize;
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+        picked
+    }
+}
@@ -0,0 +1,182 @@
+use crate::beacon::{Beacon, Room};
+use crate::signal::Signal;
+use log::info;
+
+/// Distance in metres below which beacons vote with the same weight.
+const MIN_VOTE_DISTANCE: f64 = 0.5;
+
+/// Determines the room by letting every beacon heard vote for its own.
+///
+/// A beacon votes with the number of signals it was aggregated from over its squared
+/// distance. The floor with most votes wins, then the room with most votes on it. To
+/// keep the room from flipping near stairwells, a different room only takes over once
+/// it has outvoted the current one by `margin` for `confirmations` batches in a row.
+#[derive(Debug, Clone)]
+pub struct RoomResolver {
+    margin: f64,
+    confirmations: usize,
+    current: Option<Room>,
+    candidate: Option<(Room, usize)>,
+}
+
+impl Default for RoomResolver {
+    fn default() -> Self {
+        Self {
+            margin: 1.5,
+            confirmations: 2,
+            current: None,
+            candidate: None,
+        }
+    }
+}
+
+impl RoomResolver {
+    /// Factor by which another room has to outvote the current one.
+    pub fn margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Consecutive batches another room has to win before it takes over.
+    pub fn confirmations(mut self, confirmations: usize) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// The room of the tracker given signals with their distances.
+    pub(crate) fn resolve<'a>(
+        &mut self,
+        signals: impl IntoIterator<Item = &'a Signal<Beacon>>,
+    ) -> Option<Room> {
+        let votes: Vec<(&Room, f64)> = signals
+            .into_iter()
+            .map(|s| {
+                let count = s.stats.as_ref().map_or(1, |stats| stats.count.max(1)) as f64;
+                let distance = s.distance.unwrap_or(f64::MAX).max(MIN_VOTE_DISTANCE);
+                (&s.beacon.location, count / (distance * distance))
+            })
+            .collect();
+        let score = |predicate: &dyn Fn(&Room) -> bool| -> f64 {
+            votes
+                .iter()
+                .filter(|(r, _)| predicate(r))
+                .map(|(_, v)| v)
+                .sum()
+        };
+
+        let floor = votes
+            .iter()
+            .map(|(r, _)| (*r, score(&|other| same_floor(r, other))))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?
+            .0;
+        let (winner, winner_score) = votes
+            .iter()
+            .filter(|(r, _)| same_floor(r, floor))
+            .map(|(r, _)| (*r, score(&|other| other == *r)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        let Some(current) = &self.current else {
+            self.current = Some(winner.clone());
+            return self.current.clone();
+        };
+        if current == winner {
+            self.candidate = None;
+            return self.current.clone();
+        }
+
+        let current_score = score(&|other| other == current);
+        if current_score > 0.0 && winner_score < self.margin * current_score {
+            self.candidate = None;
+            return self.current.clone();
+        }
+
+        let confirmed = match &mut self.candidate {
+            Some((room, count)) if room == winner => {
+                *count += 1;
+                *count
+            }
+            _ => {
+                self.candidate = Some((winner.clone(), 1));
+                1
+            }
+        };
+        // the current room is not heard at all, so there is nothing to hold on to
+        if confirmed >= self.confirmations || current_score == 0.0 {
+            info!(
+                "room changed from {} to {}",
+                current.identifier(),
+                winner.identifier()
+            );
+            self.current = Some(winner.clone());
+            self.candidate = None;
+        }
+        self.current.clone()
+    }
+}
+
+pub(crate) fn same_floor(a: &Room, b: &Room) -> bool {
+    a.building == b.building && a.floor == b.floor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon::BeaconId;
+    use crate::clock::ManualClock;
+    use crate::geographic::Position;
+    use crate::level::Level;
+
+    fn signal(floor: &str, room: &str, distance: f64) -> Signal<Beacon> {
+        let clock = ManualClock::default();
+        let beacon = Beacon::new(
+            BeaconId::new("uuid", 1, 1),
+            Room::new("HG", Level::parse("HG", floor).unwrap(), room),
+            Position::new(47.3764, 8.5477),
+        );
+        Signal::new(beacon, -59, -70, &clock).with_distance(distance)
+    }
+
+    #[test]
+    fn test_votes_across_beacons() {
+        let mut resolver = RoomResolver::default();
+        let signals = vec![
+            // a single beacon through the stairwell is nearest
+            signal("F", "1", 2.0),
+            signal("E", "12", 2.5),
+            signal("E", "12", 3.0),
+            signal("E", "14", 3.0),
+        ];
+
+        let room = resolver.resolve(&signals).unwrap();
+        assert_eq!(room, Room::new("HG", Level::new(0).unwrap(), "12"));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut resolver = RoomResolver::default();
+        let downstairs = vec![signal("E", "12", 2.0), signal("F", "1", 3.0)];
+        let upstairs = vec![signal("E", "12", 3.0), signal("F", "1", 1.5)];
+
+        assert_eq!(
+            resolver.resolve(&downstairs).unwrap().floor,
+            Some(Level::new(0).unwrap())
+        );
+        // a single batch favouring the floor above does not flip it
+        assert_eq!(
+            resolver.resolve(&upstairs).unwrap().floor,
+            Some(Level::new(0).unwrap())
+        );
+        assert_eq!(
+            resolver.resolve(&downstairs).unwrap().floor,
+            Some(Level::new(0).unwrap())
+        );
+        assert_eq!(
+            resolver.resolve(&upstairs).unwrap().floor,
+            Some(Level::new(0).unwrap())
+        );
+        assert_eq!(
+            resolver.resolve(&upstairs).unwrap().floor,
+            Some(Level::new(1).unwrap())
+        );
+    }
+}
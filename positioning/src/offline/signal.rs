@@ -1,21 +1,244 @@
-const N: f64 = 3.5;
+/// Shadowing of indoor 2.4 GHz links in dB², present even in a perfectly averaged RSSI.
+pub const SHADOWING_VARIANCE: f64 = 16.0;
 
-pub fn calculate_distance(rssi: i8, tx: i8) -> f64 {
-    let diff = (tx - rssi) as f64;
-    10f64.powf(diff / (10.0 * N))
+/// A distance in metres together with its variance in m².
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceEstimate {
+    pub distance: f64,
+    pub variance: f64,
+}
+
+/// Converts a received signal strength into a distance in metres.
+///
+/// `tx_power` is the calibrated RSSI at one metre as advertised by the beacon.
+pub trait PathLossModel: Send {
+    fn distance(&self, rssi: i8, tx_power: i8) -> f64;
+
+    /// Expected RSSI at the given distance, the inverse of [`PathLossModel::distance`].
+    fn rssi(&self, distance: f64, tx_power: i8) -> f64;
+
+    /// Distance and its variance for an RSSI with the given variance in dB².
+    ///
+    /// The variance is propagated with the slope of the model (delta method), so it
+    /// grows with the range. The default takes a central difference over ±1 dB.
+    fn estimate(&self, rssi: i8, tx_power: i8, rssi_variance: f64) -> DistanceEstimate {
+        let slope = (self.distance(rssi.saturating_sub(1), tx_power)
+            - self.distance(rssi.saturating_add(1), tx_power))
+            / 2.0;
+        DistanceEstimate {
+            distance: self.distance(rssi, tx_power),
+            variance: slope * slope * rssi_variance,
+        }
+    }
+}
+
+/// Log-distance model: the RSSI drops by `10 * exponent` dB per decade of distance.
+#[derive(Debug, Clone, Copy)]
+pub struct LogDistance {
+    exponent: f64,
+    /// Distance in metres at which `tx_power` was measured.
+    reference_distance: f64,
+}
+
+impl Default for LogDistance {
+    fn default() -> Self {
+        Self::new(3.5)
+    }
+}
+
+impl LogDistance {
+    pub fn new(exponent: f64) -> Self {
+        Self {
+            exponent,
+            reference_distance: 1.0,
+        }
+    }
+
+    pub fn reference_distance(mut self, reference_distance: f64) -> Self {
+        self.reference_distance = reference_distance;
+        self
+    }
+}
+
+impl PathLossModel for LogDistance {
+    fn distance(&self, rssi: i8, tx_power: i8) -> f64 {
+        let diff = tx_power as f64 - rssi as f64;
+        self.reference_distance * 10f64.powf(diff / (10.0 * self.exponent))
+    }
+
+    fn rssi(&self, distance: f64, tx_power: i8) -> f64 {
+        tx_power as f64 - 10.0 * self.exponent * (distance / self.reference_distance).log10()
+    }
+
+    fn estimate(&self, rssi: i8, tx_power: i8, rssi_variance: f64) -> DistanceEstimate {
+        let distance = self.distance(rssi, tx_power);
+        let slope = distance * std::f64::consts::LN_10 / (10.0 * self.exponent);
+        DistanceEstimate {
+            distance,
+            variance: slope * slope * rssi_variance,
+        }
+    }
+}
+
+/// ITU-R P.1238 indoor model relative to the one metre reference.
+///
+/// The frequency term cancels against `tx_power`, leaving the distance power loss
+/// coefficient and the penetration loss of the floors between beacon and receiver.
+#[derive(Debug, Clone, Copy)]
+pub struct ItuIndoor {
+    /// Distance power loss coefficient `N`, 30 for offices at 2.4 GHz.
+    power_loss: f64,
+    floors: u8,
+}
+
+impl Default for ItuIndoor {
+    fn default() -> Self {
+        Self {
+            power_loss: 30.0,
+            floors: 0,
+        }
+    }
+}
+
+impl ItuIndoor {
+    pub fn new(power_loss: f64) -> Self {
+        Self {
+            power_loss,
+            ..Self::default()
+        }
+    }
+
+    /// Number of floors between beacon and receiver.
+    pub fn floors(mut self, floors: u8) -> Self {
+        self.floors = floors;
+        self
+    }
+
+    /// Floor penetration loss factor for offices at 2.4 GHz, in dB.
+    fn floor_penetration(&self) -> f64 {
+        match self.floors {
+            0 => 0.0,
+            n => 15.0 + 4.0 * (n as f64 - 1.0),
+        }
+    }
+}
+
+impl PathLossModel for ItuIndoor {
+    fn distance(&self, rssi: i8, tx_power: i8) -> f64 {
+        let loss = tx_power as f64 - rssi as f64 - self.floor_penetration();
+        10f64.powf(loss / self.power_loss)
+    }
+
+    fn rssi(&self, distance: f64, tx_power: i8) -> f64 {
+        tx_power as f64 - self.power_loss * distance.log10() - self.floor_penetration()
+    }
+}
+
+/// Empirically fitted model: `log10(distance)` as a polynomial of the path loss.
+///
+/// The path loss is `tx_power - rssi` in dB. Coefficients are ordered by ascending
+/// power, so `[0.0, 1.0 / 35.0]` is the log-distance model with exponent 3.5.
+#[derive(Debug, Clone)]
+pub struct Polynomial {
+    coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<f64>) -> Self {
+        Self { coefficients }
+    }
+}
+
+impl Polynomial {
+    fn log_distance(&self, loss: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| acc * loss + c)
+    }
+}
+
+impl PathLossModel for Polynomial {
+    fn distance(&self, rssi: i8, tx_power: i8) -> f64 {
+        10f64.powf(self.log_distance(tx_power as f64 - rssi as f64))
+    }
+
+    /// Bisects the path loss, assuming the fit increases monotonically with it.
+    fn rssi(&self, distance: f64, tx_power: i8) -> f64 {
+        let target = distance.log10();
+        let (mut low, mut high) = (-60.0, 160.0);
+        for _ in 0..50 {
+            let mid = (low + high) / 2.0;
+            if self.log_distance(mid) < target {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        tx_power as f64 - (low + high) / 2.0
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
     #[test]
     fn test_1m_distance() {
-        assert_eq!(calculate_distance(-77, -77), 1f64);
+        assert_eq!(LogDistance::default().distance(-77, -77), 1f64);
     }
 
     #[test]
     fn test_3m_distance() {
-        assert_eq!(calculate_distance(-94, -77), 3.0599496f64);
+        assert_close(LogDistance::default().distance(-94, -77), 3.0599497);
+    }
+
+    #[test]
+    fn test_rssi_inverts_distance() {
+        let models: [Box<dyn PathLossModel>; 3] = [
+            Box::new(LogDistance::new(2.8).reference_distance(2.0)),
+            Box::new(ItuIndoor::default().floors(2)),
+            Box::new(Polynomial::new(vec![0.1, 0.02, 0.0002])),
+        ];
+        for model in &models {
+            let distance = model.distance(-90, -60);
+            assert_close(model.rssi(distance, -60), -90.0);
+        }
+    }
+
+    #[test]
+    fn test_variance_grows_with_range() {
+        let model = LogDistance::default();
+        let near = model.estimate(-77, -77, SHADOWING_VARIANCE);
+        let far = model.estimate(-94, -77, SHADOWING_VARIANCE);
+        assert!(far.variance > 9.0 * near.variance);
+
+        let numeric = ItuIndoor::new(35.0).estimate(-94, -77, SHADOWING_VARIANCE);
+        assert!((numeric.variance / far.variance - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_itu_floor_penetration() {
+        let model = ItuIndoor::default();
+        assert_close(model.distance(-107, -77), 10.0);
+        assert_close(model.floors(1).distance(-122, -77), 10.0);
+    }
+
+    #[test]
+    fn test_polynomial_matches_log_distance() {
+        let model = Polynomial::new(vec![0.0, 1.0 / 35.0]);
+        assert_close(
+            model.distance(-94, -77),
+            LogDistance::default().distance(-94, -77),
+        );
     }
 }
@@ -0,0 +1,261 @@
+use crate::beacon::Output;
+use crate::geographic::{Enu, LocalFrame, Position};
+use crate::pipeline::{BoundedSender, Stage};
+use chrono::{DateTime, Utc};
+use crossbeam_channel::{Receiver, select};
+use log::{error, info};
+
+/// Variance in m² assumed for fixes that do not report their accuracy.
+const DEFAULT_FIX_VARIANCE: f64 = 25.0;
+/// Speed in m/s below which the heading is left out as noise.
+const MIN_HEADING_SPEED: f64 = 0.2;
+/// Gap in seconds after which the filter starts over instead of predicting.
+const MAX_GAP: f64 = 60.0;
+
+/// Position and velocity along one axis with their covariance.
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    position: f64,
+    velocity: f64,
+    pp: f64,
+    pv: f64,
+    vv: f64,
+}
+
+impl Axis {
+    fn new(position: f64, variance: f64, velocity_variance: f64) -> Self {
+        Self {
+            position,
+            velocity: 0.0,
+            pp: variance,
+            pv: 0.0,
+            vv: velocity_variance,
+        }
+    }
+
+    /// Constant velocity with white acceleration of spectral density `q` in m²/s³.
+    fn predict(&mut self, dt: f64, q: f64) {
+        self.position += self.velocity * dt;
+        self.pp += dt * (2.0 * self.pv + dt * self.vv) + q * dt.powi(3) / 3.0;
+        self.pv += dt * self.vv + q * dt.powi(2) / 2.0;
+        self.vv += q * dt;
+    }
+
+    fn correct(&mut self, measured: f64, variance: f64) {
+        let innovation = measured - self.position;
+        let s = self.pp + variance;
+        let (kp, kv) = (self.pp / s, self.pv / s);
+        self.position += kp * innovation;
+        self.velocity += kv * innovation;
+        let (pp, pv) = (self.pp, self.pv);
+        self.pp -= kp * pp;
+        self.pv -= kp * pv;
+        self.vv -= kv * pv;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct State {
+    frame: LocalFrame,
+    east: Axis,
+    north: Axis,
+    updated: DateTime<Utc>,
+}
+
+/// Smooths consecutive fixes with a constant-velocity Kalman filter.
+///
+/// Fills in the speed in m/s and the heading in degrees clockwise from north, and
+/// drops fixes that could only be reached faster than `max_speed`. After
+/// `max_rejections` drops in a row the filter trusts the fixes again and starts over,
+/// e.g. after a wrong first fix.
+#[derive(Debug, Clone)]
+pub struct PositionFilter {
+    /// Spectral density of the acceleration in m²/s³.
+    acceleration_noise: f64,
+    max_speed: f64,
+    max_rejections: usize,
+    rejections: usize,
+    state: Option<State>,
+}
+
+impl Default for PositionFilter {
+    fn default() -> Self {
+        Self {
+            acceleration_noise: 0.5,
+            max_speed: 3.0,
+            max_rejections: 3,
+            rejections: 0,
+            state: None,
+        }
+    }
+}
+
+impl PositionFilter {
+    pub fn acceleration_noise(mut self, acceleration_noise: f64) -> Self {
+        self.acceleration_noise = acceleration_noise;
+        self
+    }
+
+    /// Fastest plausible speed in m/s, running indoors by default.
+    pub fn max_speed(mut self, max_speed: f64) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+
+    pub fn max_rejections(mut self, max_rejections: usize) -> Self {
+        self.max_rejections = max_rejections;
+        self
+    }
+
+    /// Filters a fix, `None` if it is rejected as an impossible jump.
+    pub fn update(&mut self, output: Output) -> Option<Output> {
+        let variance = output
+            .accuracy
+            .map_or(DEFAULT_FIX_VARIANCE, |a| a * a)
+            .max(0.01);
+
+        let Some(mut state) = self.state.filter(|s| {
+            let dt = (output.timestamp - s.updated).as_seconds_f64();
+            (0.0..MAX_GAP).contains(&dt) && self.rejections < self.max_rejections
+        }) else {
+            return Some(self.reset(output, variance));
+        };
+
+        let dt = (output.timestamp - state.updated).as_seconds_f64();
+        let measured = state.frame.to_local(output.position);
+        let previous = Enu::new(state.east.position, state.north.position);
+        let jump = measured.distance(previous) - variance.sqrt();
+        if jump > self.max_speed * dt.max(1.0) {
+            self.rejections += 1;
+            info!(
+                "rejecting jump of {:.1} m in {:.1} s",
+                measured.distance(previous),
+                dt
+            );
+            return None;
+        }
+        self.rejections = 0;
+
+        for (axis, measured) in [
+            (&mut state.east, measured.east),
+            (&mut state.north, measured.north),
+        ] {
+            axis.predict(dt, self.acceleration_noise);
+            axis.correct(measured, variance);
+        }
+        state.updated = output.timestamp;
+        self.state = Some(state);
+
+        Some(Self::publish(&state, output))
+    }
+
+    fn reset(&mut self, output: Output, variance: f64) -> Output {
+        let velocity_variance = self.max_speed * self.max_speed;
+        let state = State {
+            frame: LocalFrame::new(output.position),
+            east: Axis::new(0.0, variance, velocity_variance),
+            north: Axis::new(0.0, variance, velocity_variance),
+            updated: output.timestamp,
+        };
+        self.state = Some(state);
+        self.rejections = 0;
+        Output {
+            speed: None,
+            heading: None,
+            ..output
+        }
+    }
+
+    fn publish(state: &State, output: Output) -> Output {
+        let position = Enu::new(state.east.position, state.north.position);
+        let speed = state.east.velocity.hypot(state.north.velocity);
+        let heading = (speed >= MIN_HEADING_SPEED).then(|| {
+            let degrees = state.east.velocity.atan2(state.north.velocity).to_degrees();
+            degrees.rem_euclid(360.0).round() as i32 % 360
+        });
+        Output {
+            position: Position {
+                alt: output.position.alt,
+                ..state.frame.to_global(position)
+            },
+            speed: Some(speed as f32),
+            heading,
+            ..output
+        }
+        .with_accuracy((state.east.pp + state.north.pp).sqrt())
+    }
+
+    pub fn start(self, rx: Receiver<Output>, tx: BoundedSender<Output>) -> anyhow::Result<Stage> {
+        Stage::spawn("position filter", 8 * 1024, move |stop| {
+            let mut filter = self;
+
+            loop {
+                select! {
+                    recv(rx) -> msg => match msg {
+                        Ok(output) => {
+                            let Some(output) = filter.update(output) else {
+                                continue;
+                            };
+                            if let Err(e) = tx.send(output) {
+                                error!("Failed to send filtered position: {}", e);
+                            }
+                        }
+                        Err(_) => break,
+                    },
+                    recv(stop) -> _ => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon::Room;
+    use crate::level::Level;
+    use chrono::TimeDelta;
+
+    fn fix(frame: &LocalFrame, east: f64, north: f64, seconds: i64) -> Output {
+        let timestamp = DateTime::<Utc>::UNIX_EPOCH + TimeDelta::seconds(seconds);
+        Output::new(
+            frame.to_global(Enu::new(east, north)),
+            Room::new("HG", Level::new(0).unwrap(), "12"),
+            None,
+            None,
+        )
+        .with_accuracy(1.0)
+        .with_timestamp(timestamp)
+    }
+
+    #[test]
+    fn test_estimates_walking_velocity() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let mut filter = PositionFilter::default();
+
+        let mut output = None;
+        for i in 0..20 {
+            // walking east at 1.2 m/s
+            output = filter.update(fix(&frame, 1.2 * 2.0 * i as f64, 0.0, 2 * i));
+        }
+
+        let output = output.unwrap();
+        let speed = output.speed.unwrap();
+        assert!((speed - 1.2).abs() < 0.1, "speed {}", speed);
+        assert_eq!(output.heading, Some(90));
+    }
+
+    #[test]
+    fn test_rejects_impossible_jump() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let mut filter = PositionFilter::default().max_rejections(2);
+
+        assert!(filter.update(fix(&frame, 0.0, 0.0, 0)).is_some());
+        assert!(filter.update(fix(&frame, 0.5, 0.0, 2)).is_some());
+        assert!(filter.update(fix(&frame, 40.0, 0.0, 4)).is_none());
+        assert!(filter.update(fix(&frame, 40.0, 0.0, 6)).is_none());
+        // consistently elsewhere, so start over there
+        let output = filter.update(fix(&frame, 40.0, 0.0, 8)).unwrap();
+        assert_eq!(output.speed, None);
+    }
+}
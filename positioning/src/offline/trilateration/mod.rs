@@ -0,0 +1,274 @@
+mod ransac;
+mod solver;
+
+use log::info;
+use std::time::Instant;
+
+use crate::geographic::{Enu, LocalFrame, Position};
+
+pub use ransac::Ransac;
+pub use solver::{
+    GaussNewton, LevenbergMarquardt, LinearLeastSquares, NelderMead, Range, Solution, Solver,
+    covariance, dilution_of_precision,
+};
+
+/// How the height of the tracker enters the trilateration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackerHeight {
+    /// Carried at a known height in metres above the floor, e.g. 1 m in a pocket.
+    Fixed(f64),
+    /// Solved for along with the position, starting at the given height in metres
+    /// above the floor.
+    Estimate(f64),
+}
+
+impl TrackerHeight {
+    /// Known or initially assumed height in metres above the floor.
+    pub fn above_floor(&self) -> f64 {
+        match self {
+            TrackerHeight::Fixed(h) | TrackerHeight::Estimate(h) => *h,
+        }
+    }
+}
+
+impl Default for TrackerHeight {
+    fn default() -> Self {
+        TrackerHeight::Fixed(1.0)
+    }
+}
+
+/// Result of a trilateration.
+#[derive(Debug, Clone)]
+pub(crate) struct Fix {
+    pub position: Position,
+    pub cost: f64,
+    /// Root of the summed horizontal position variances in metres.
+    pub accuracy: Option<f64>,
+    /// Horizontal dilution of precision, `None` if the geometry leaves an axis unconstrained.
+    pub dop: Option<f64>,
+    /// Indices of the measurements rejected as outliers.
+    pub outliers: Vec<usize>,
+}
+
+pub(crate) struct Measurement {
+    pub lat: f64,
+    pub lon: f64,
+    /// Height of the beacon in metres above level zero, if known.
+    pub alt: Option<f64>,
+    pub distance: f64,
+    pub weight: f64,
+}
+
+impl Measurement {
+    pub fn new(lat: f64, lon: f64, distance: f64) -> Self {
+        Self {
+            lat,
+            lon,
+            alt: None,
+            distance,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_altitude(mut self, alt: f64) -> Self {
+        self.alt = Some(alt);
+        self
+    }
+
+    /// Weighs the measurement by the inverse of the distance variance in m².
+    pub fn with_variance(mut self, variance: f64) -> Self {
+        self.weight = 1.0 / variance.max(f64::EPSILON);
+        self
+    }
+}
+
+/// Weighted mean of the beacon positions, `None` without measurements.
+pub(crate) fn weighted_centroid(measurements: &[Measurement]) -> Option<Position> {
+    let total: f64 = measurements.iter().map(|m| m.weight).sum();
+    if measurements.is_empty() || total <= 0.0 {
+        return None;
+    }
+
+    let lat = measurements.iter().map(|m| m.weight * m.lat).sum::<f64>() / total;
+    let lon = measurements.iter().map(|m| m.weight * m.lon).sum::<f64>() / total;
+    Some(Position::new(lat, lon))
+}
+
+/// Finds the position best matching the measured distances.
+///
+/// The search starts at `start`, e.g. the previous fix, or else at the weighted
+/// centroid of the beacons. It runs in a local frame anchored there, with heights
+/// relative to `floor`, the height of the tracker's floor above level zero.
+///
+/// At a fixed tracker height the distances to beacons of known height are reduced to
+/// their horizontal part. Otherwise the height is solved for as a third coordinate;
+/// as ceiling-mounted beacons cannot tell above from below, the tracker is then kept
+/// below the highest beacon.
+pub fn trilaterate(
+    solver: &dyn Solver,
+    measurements: Vec<Measurement>,
+    start: Option<Position>,
+    height: TrackerHeight,
+    floor: f64,
+) -> anyhow::Result<Fix> {
+    let origin = start
+        .or_else(|| weighted_centroid(&measurements))
+        .ok_or_else(|| anyhow::anyhow!("no measurements to trilaterate"))?;
+    let frame = LocalFrame::new(origin);
+
+    let tracker = floor + height.above_floor();
+    let estimate = matches!(height, TrackerHeight::Estimate(_))
+        && measurements.iter().any(|m| m.alt.is_some());
+    let ranges: Vec<_> = measurements
+        .iter()
+        .map(|m| {
+            let anchor = frame.to_local(Position::new(m.lat, m.lon));
+            let up = m.alt.map_or(0.0, |alt| alt - tracker);
+            if estimate {
+                return Range::new(vec![anchor.east, anchor.north, up], m.distance, m.weight);
+            }
+
+            let horizontal = (m.distance.powi(2) - up.powi(2)).max(0.0).sqrt();
+            // the horizontal part varies more than the distance, by d / h
+            let shrink = (horizontal / m.distance.max(f64::EPSILON)).max(0.1);
+            Range::new(
+                vec![anchor.east, anchor.north],
+                horizontal,
+                m.weight * shrink * shrink,
+            )
+        })
+        .collect();
+
+    let started = Instant::now();
+    info!("Starting trilateration with {} measurements.", ranges.len());
+    let start = if estimate { vec![0.0; 3] } else { vec![0.0; 2] };
+    let mut solution = solver.solve(&ranges, &start)?;
+    if estimate {
+        let ceiling = ranges.iter().map(|r| r.anchor[2]).fold(f64::MIN, f64::max);
+        if solution.position[2] > ceiling {
+            solution.position[2] = 2.0 * ceiling - solution.position[2];
+        }
+    }
+
+    info!("Elapsed time: {:?}", started.elapsed());
+    info!("Best solution: {:?}", solution.position);
+    info!("Best function value: {:?}", solution.cost);
+    info!("Iterations: {:?}", solution.iterations);
+
+    let inliers: Vec<Range> = ranges
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !solution.outliers.contains(i))
+        .map(|(_, r)| r)
+        .collect();
+    let accuracy = covariance(&inliers, &solution.position).map(|c| (c[0][0] + c[1][1]).sqrt());
+    let dop = dilution_of_precision(&inliers, &solution.position);
+
+    let alt = solution.position.get(2).map_or(tracker, |up| tracker + up);
+    Ok(Fix {
+        position: frame
+            .to_global(Enu::new(solution.position[0], solution.position[1]))
+            .with_altitude(alt),
+        cost: solution.cost,
+        accuracy,
+        dop,
+        outliers: solution.outliers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geographic::haversine_distance;
+    use crate::offline::GeometryGate;
+
+    #[test]
+    fn test_converges_near_beacons() {
+        let target = Position::new(47.37640, 8.54770);
+        let beacons = [
+            Position::new(47.37630, 8.54760),
+            Position::new(47.37650, 8.54760),
+            Position::new(47.37640, 8.54790),
+        ];
+        let measurements = beacons
+            .iter()
+            .map(|b| Measurement::new(b.lat, b.lon, haversine_distance(target, *b)))
+            .collect();
+
+        let fix = trilaterate(
+            &NelderMead::default(),
+            measurements,
+            None,
+            TrackerHeight::default(),
+            0.0,
+        )
+        .unwrap();
+        assert!(haversine_distance(fix.position, target) < 0.1);
+        assert!(fix.accuracy.is_some_and(|a| a < 0.1));
+    }
+
+    #[test]
+    fn test_ceiling_beacons() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let target = Enu::new(4.0, 3.0);
+        let beacons = [
+            Enu::new(0.0, 0.0),
+            Enu::new(10.0, 0.0),
+            Enu::new(10.0, 8.0),
+            Enu::new(0.0, 8.0),
+            Enu::new(5.0, 4.0),
+        ];
+        // on the ceiling of the first floor, 1.7 m above the tracker
+        let measurements = || {
+            beacons
+                .iter()
+                .map(|b| {
+                    let p = frame.to_global(*b);
+                    let d = target.distance(*b).hypot(1.7);
+                    Measurement::new(p.lat, p.lon, d).with_altitude(5.0 + 2.7)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for height in [TrackerHeight::Fixed(1.0), TrackerHeight::Estimate(1.5)] {
+            let solver = LevenbergMarquardt::default();
+            let fix = trilaterate(&solver, measurements(), None, height, 5.0).unwrap();
+
+            let error = haversine_distance(fix.position, frame.to_global(target));
+            assert!(error < 0.05, "{:?} error {}", height, error);
+            let alt = fix.position.alt.unwrap();
+            assert!((alt - 6.0).abs() < 0.05, "{:?} altitude {}", height, alt);
+        }
+    }
+
+    #[test]
+    fn test_estimated_height_passes_geometry_gate() {
+        let frame = LocalFrame::new(Position::new(47.3764, 8.5477));
+        let target = Enu::new(15.0, 12.0);
+        // a hall with a beacon in each corner of the ceiling, far from the tracker
+        let measurements = [
+            Enu::new(0.0, 0.0),
+            Enu::new(30.0, 0.0),
+            Enu::new(30.0, 24.0),
+            Enu::new(0.0, 24.0),
+        ]
+        .iter()
+        .map(|b| {
+            let p = frame.to_global(*b);
+            Measurement::new(p.lat, p.lon, target.distance(*b).hypot(1.7)).with_altitude(2.7)
+        })
+        .collect();
+
+        let solver = LevenbergMarquardt::default();
+        let height = TrackerHeight::Estimate(1.5);
+        let fix = trilaterate(&solver, measurements, None, height, 0.0).unwrap();
+
+        // the poorly constrained height does not count against the geometry
+        assert_eq!(
+            GeometryGate::default().check(fix.dop),
+            None,
+            "dop {:?}",
+            fix.dop
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::offline::random::XorShift;
+use crate::offline::trilateration::solver::{LinearLeastSquares, Range, Solution, Solver, cost};
+use log::debug;
+
+/// Robust wrapper around another solver that leaves out inconsistent ranges.
+///
+/// Repeatedly solves random minimal subsets in closed form and counts the ranges whose
+/// residual is within `threshold` standard deviations, given by the range weights. The
+/// inner solver then refits the largest consensus set.
+#[derive(Debug, Clone, Copy)]
+pub struct Ransac<S> {
+    inner: S,
+    iterations: usize,
+    threshold: f64,
+}
+
+impl<S: Solver> Ransac<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            iterations: 30,
+            threshold: 3.0,
+        }
+    }
+
+    /// Number of minimal subsets tried.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Residual in standard deviations up to which a range supports a solution.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    fn inliers(&self, ranges: &[Range], x: &[f64]) -> Vec<usize> {
+        (0..ranges.len())
+            .filter(|&i| ranges[i].residual(x).abs() * ranges[i].weight.sqrt() <= self.threshold)
+            .collect()
+    }
+}
+
+impl<S: Solver> Solver for Ransac<S> {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution> {
+        let minimal = start.len() + 1;
+        if ranges.len() <= minimal {
+            // without redundancy no range can be told apart as an outlier
+            return self.inner.solve(ranges, start);
+        }
+
+        let mut rng = XorShift::new(0x9e37_79b9_7f4a_7c15 ^ ranges.len() as u64);
+        let mut best: Option<(Vec<usize>, Vec<f64>)> = None;
+        for _ in 0..self.iterations {
+            let subset: Vec<Range> = rng
+                .sample(ranges.len(), minimal)
+                .into_iter()
+                .map(|i| ranges[i].clone())
+                .collect();
+            let Ok(candidate) = LinearLeastSquares.solve(&subset, start) else {
+                continue;
+            };
+
+            let inliers = self.inliers(ranges, &candidate.position);
+            let better = best.as_ref().is_none_or(|(best_inliers, best_position)| {
+                inliers.len() > best_inliers.len()
+                    || inliers.len() == best_inliers.len()
+                        && cost(ranges, &candidate.position) < cost(ranges, best_position)
+            });
+            if better {
+                best = Some((inliers, candidate.position));
+            }
+        }
+
+        let Some((inliers, seed)) = best.filter(|(inliers, _)| inliers.len() >= minimal) else {
+            debug!("no consensus among {} ranges", ranges.len());
+            return self.inner.solve(ranges, start);
+        };
+
+        let consistent: Vec<Range> = inliers.iter().map(|&i| ranges[i].clone()).collect();
+        let mut solution = self.inner.solve(&consistent, &seed)?;
+        solution.outliers = (0..ranges.len()).filter(|i| !inliers.contains(i)).collect();
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offline::trilateration::solver::LevenbergMarquardt;
+
+    #[test]
+    fn test_rejects_moved_beacon() {
+        let target = [4.0f64, 3.0];
+        let anchors = [
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 8.0],
+            [0.0, 8.0],
+            [5.0, -2.0],
+        ];
+        let mut ranges: Vec<_> = anchors
+            .iter()
+            .map(|a| {
+                let d = (target[0] - a[0]).hypot(target[1] - a[1]);
+                Range::new(a.to_vec(), d, 1.0)
+            })
+            .collect();
+        // moved away by eight metres
+        ranges[2].distance += 8.0;
+
+        let solution = Ransac::new(LevenbergMarquardt::default())
+            .solve(&ranges, &[5.0, 4.0])
+            .unwrap();
+
+        assert_eq!(solution.outliers, vec![2]);
+        let error = (solution.position[0] - target[0]).hypot(solution.position[1] - target[1]);
+        assert!(error < 0.01, "error {}", error);
+    }
+}
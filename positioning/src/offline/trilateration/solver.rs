@@ -0,0 +1,533 @@
+use anyhow::{anyhow, bail};
+use argmin::core::{CostFunction, Error, Executor};
+
+/// Distance to an anchor at known coordinates in a metric frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    pub anchor: Vec<f64>,
+    pub distance: f64,
+    pub weight: f64,
+}
+
+impl Range {
+    pub fn new(anchor: Vec<f64>, distance: f64, weight: f64) -> Self {
+        Self {
+            anchor,
+            distance,
+            weight,
+        }
+    }
+
+    pub fn residual(&self, x: &[f64]) -> f64 {
+        norm(&difference(x, &self.anchor)) - self.distance
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub position: Vec<f64>,
+    /// Weighted sum of the squared range residuals at `position`.
+    pub cost: f64,
+    pub iterations: u64,
+    /// Indices of the ranges left out as inconsistent.
+    pub outliers: Vec<usize>,
+}
+
+/// Minimises the weighted squared range residuals.
+///
+/// Coordinates have as many dimensions as `start`, anchors must match it.
+pub trait Solver: Send {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution>;
+}
+
+/// Weighted sum of the squared range residuals at `x`.
+pub fn cost(ranges: &[Range], x: &[f64]) -> f64 {
+    ranges
+        .iter()
+        .map(|r| r.weight * r.residual(x).powi(2))
+        .sum()
+}
+
+/// Covariance of the position at `x`, scaled by the residuals when the ranges
+/// overdetermine it. `None` if the geometry does not constrain every axis.
+pub fn covariance(ranges: &[Range], x: &[f64]) -> Option<Vec<Vec<f64>>> {
+    let dim = x.len();
+    let (normal, _) = linearise(ranges, x);
+    let scale = match ranges.len().checked_sub(dim) {
+        Some(redundancy) if redundancy > 0 => cost(ranges, x) / redundancy as f64,
+        _ => 1.0,
+    };
+
+    let inverse = invert(&normal)?;
+    Some(
+        inverse
+            .into_iter()
+            .map(|row| row.into_iter().map(|v| v * scale).collect())
+            .collect(),
+    )
+}
+
+/// Horizontal dilution of precision of the anchors as seen from `x`.
+///
+/// Depends only on the directions towards the anchors: 1 for four beacons evenly
+/// around the position in 2D, growing without bound as they line up. A third,
+/// vertical axis is left out, as ceiling-mounted beacons barely constrain it. `None`
+/// if the anchors do not constrain every axis.
+pub fn dilution_of_precision(ranges: &[Range], x: &[f64]) -> Option<f64> {
+    let unweighted: Vec<Range> = ranges
+        .iter()
+        .map(|r| Range::new(r.anchor.clone(), r.distance, 1.0))
+        .collect();
+    let (normal, _) = linearise(&unweighted, x);
+    let inverse = invert(&normal)?;
+    Some((inverse[0][0] + inverse[1][1]).sqrt())
+}
+
+/// Derivative-free simplex search.
+#[derive(Debug, Clone, Copy)]
+pub struct NelderMead {
+    /// Edge length of the initial simplex in metres.
+    simplex_size: f64,
+    /// Standard deviation of the simplex costs below which the solver has converged.
+    sd_tolerance: f64,
+    max_iters: u64,
+}
+
+impl Default for NelderMead {
+    fn default() -> Self {
+        Self {
+            simplex_size: 3.0,
+            sd_tolerance: 1e-6,
+            max_iters: 200,
+        }
+    }
+}
+
+struct Quadratic<'a> {
+    ranges: &'a [Range],
+}
+
+impl CostFunction for Quadratic<'_> {
+    type Param = Vec<f64>;
+    type Output = f64;
+
+    fn cost(&self, x: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(cost(self.ranges, x))
+    }
+}
+
+impl Solver for NelderMead {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution> {
+        // right-angled simplex at the start with legs along each axis
+        let mut simplex = vec![start.to_vec()];
+        for axis in 0..start.len() {
+            let mut vertex = start.to_vec();
+            vertex[axis] += self.simplex_size;
+            simplex.push(vertex);
+        }
+
+        let solver = argmin::solver::neldermead::NelderMead::new(simplex)
+            .with_sd_tolerance(self.sd_tolerance)?;
+        let res = Executor::new(Quadratic { ranges }, solver)
+            .configure(|cfg| cfg.max_iters(self.max_iters))
+            .run()?;
+
+        let state = res.state();
+        let position = state
+            .best_param
+            .clone()
+            .ok_or_else(|| anyhow!("Optimization failed, no valid parameters found"))?;
+        Ok(Solution {
+            position,
+            cost: state.best_cost,
+            iterations: state.iter,
+            outliers: Vec::new(),
+        })
+    }
+}
+
+/// Closed-form solution of the ranges linearised against the most trusted one.
+///
+/// Subtracting the squared range equation of a reference anchor removes the quadratic
+/// term. Needs one range more than dimensions and ignores the start.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinearLeastSquares;
+
+impl Solver for LinearLeastSquares {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution> {
+        let dim = start.len();
+        if ranges.len() <= dim {
+            bail!("need {} ranges, got {}", dim + 1, ranges.len());
+        }
+
+        let reference = ranges
+            .iter()
+            .max_by(|a, b| a.weight.total_cmp(&b.weight))
+            .expect("ranges are not empty");
+        let ref_square = dot(&reference.anchor, &reference.anchor);
+
+        let mut normal = vec![vec![0.0; dim]; dim];
+        let mut rhs = vec![0.0; dim];
+        for range in ranges.iter().filter(|r| !std::ptr::eq(*r, reference)) {
+            let row: Vec<f64> = difference(&range.anchor, &reference.anchor)
+                .iter()
+                .map(|d| 2.0 * d)
+                .collect();
+            let b = reference.distance.powi(2) - range.distance.powi(2)
+                + dot(&range.anchor, &range.anchor)
+                - ref_square;
+            accumulate(&mut normal, &mut rhs, &row, b, range.weight);
+        }
+
+        let position = solve_linear(normal, rhs)
+            .ok_or_else(|| anyhow!("anchors are collinear, cannot linearise"))?;
+        Ok(Solution {
+            cost: cost(ranges, &position),
+            position,
+            iterations: 1,
+            outliers: Vec::new(),
+        })
+    }
+}
+
+/// Gauss-Newton iterations on the range residuals.
+#[derive(Debug, Clone, Copy)]
+pub struct GaussNewton {
+    /// Step length in metres below which the solver has converged.
+    tolerance: f64,
+    max_iters: u64,
+}
+
+impl Default for GaussNewton {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-4,
+            max_iters: 30,
+        }
+    }
+}
+
+impl Solver for GaussNewton {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution> {
+        let mut x = start.to_vec();
+        let mut iterations = 0;
+
+        while iterations < self.max_iters {
+            iterations += 1;
+            let (normal, gradient) = linearise(ranges, &x);
+            let step = solve_linear(normal, gradient)
+                .ok_or_else(|| anyhow!("singular normal equations"))?;
+            x = difference(&x, &step);
+            if norm(&step) < self.tolerance {
+                break;
+            }
+        }
+
+        Ok(Solution {
+            cost: cost(ranges, &x),
+            position: x,
+            iterations,
+            outliers: Vec::new(),
+        })
+    }
+}
+
+/// Gauss-Newton damped towards gradient descent while steps fail to reduce the cost.
+#[derive(Debug, Clone, Copy)]
+pub struct LevenbergMarquardt {
+    /// Step length in metres below which the solver has converged.
+    tolerance: f64,
+    max_iters: u64,
+    initial_damping: f64,
+}
+
+impl Default for LevenbergMarquardt {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-4,
+            max_iters: 50,
+            initial_damping: 1e-3,
+        }
+    }
+}
+
+impl Solver for LevenbergMarquardt {
+    fn solve(&self, ranges: &[Range], start: &[f64]) -> anyhow::Result<Solution> {
+        let mut x = start.to_vec();
+        let mut current = cost(ranges, &x);
+        let mut damping = self.initial_damping;
+        let mut iterations = 0;
+
+        while iterations < self.max_iters {
+            iterations += 1;
+            let (mut normal, gradient) = linearise(ranges, &x);
+            for (i, row) in normal.iter_mut().enumerate() {
+                row[i] += damping * row[i].max(f64::EPSILON);
+            }
+            let Some(step) = solve_linear(normal, gradient) else {
+                damping *= 10.0;
+                continue;
+            };
+
+            let candidate = difference(&x, &step);
+            let candidate_cost = cost(ranges, &candidate);
+            if candidate_cost < current {
+                x = candidate;
+                current = candidate_cost;
+                damping = (damping / 10.0).max(1e-12);
+                if norm(&step) < self.tolerance {
+                    break;
+                }
+            } else if damping > 1e12 {
+                break;
+            } else {
+                damping *= 10.0;
+            }
+        }
+
+        Ok(Solution {
+            position: x,
+            cost: current,
+            iterations,
+            outliers: Vec::new(),
+        })
+    }
+}
+
+/// Normal equations `JᵀWJ` and gradient `JᵀWr` of the residuals at `x`.
+fn linearise(ranges: &[Range], x: &[f64]) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let dim = x.len();
+    let mut normal = vec![vec![0.0; dim]; dim];
+    let mut gradient = vec![0.0; dim];
+
+    for range in ranges {
+        let offset = difference(x, &range.anchor);
+        let length = norm(&offset);
+        if length < 1e-9 {
+            // the residual is not differentiable on the anchor
+            continue;
+        }
+        let row: Vec<f64> = offset.iter().map(|o| o / length).collect();
+        accumulate(
+            &mut normal,
+            &mut gradient,
+            &row,
+            length - range.distance,
+            range.weight,
+        );
+    }
+    (normal, gradient)
+}
+
+/// Adds the weighted row `a` with target `b` to the normal equations.
+fn accumulate(normal: &mut [Vec<f64>], rhs: &mut [f64], a: &[f64], b: f64, weight: f64) {
+    for (i, ai) in a.iter().enumerate() {
+        for (j, aj) in a.iter().enumerate() {
+            normal[i][j] += weight * ai * aj;
+        }
+        rhs[i] += weight * ai * b;
+    }
+}
+
+fn invert(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let dim = matrix.len();
+    // the matrices are symmetric, so columns of the inverse equal its rows
+    (0..dim)
+        .map(|axis| {
+            let mut unit = vec![0.0; dim];
+            unit[axis] = 1.0;
+            solve_linear(matrix.to_vec(), unit)
+        })
+        .collect()
+}
+
+/// Gaussian elimination with partial pivoting, `None` if the matrix is singular.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot_value;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+fn difference(a: &[f64], b: &[f64]) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| a - b).collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offline::random::XorShift;
+    use std::time::Instant;
+
+    fn centroid(anchors: &[Vec<f64>]) -> Vec<f64> {
+        let n = anchors.len() as f64;
+        (0..2)
+            .map(|i| anchors.iter().map(|a| a[i]).sum::<f64>() / n)
+            .collect()
+    }
+
+    fn layouts() -> Vec<Vec<Vec<f64>>> {
+        vec![
+            // around the corner of a hallway; a straight corridor is left to min-max
+            vec![
+                vec![0.0, 0.0],
+                vec![10.0, 0.0],
+                vec![20.0, 0.0],
+                vec![20.0, 10.0],
+            ],
+            // room with a beacon in each corner
+            vec![
+                vec![0.0, 0.0],
+                vec![12.0, 0.0],
+                vec![12.0, 8.0],
+                vec![0.0, 8.0],
+            ],
+            // irregular hall
+            vec![
+                vec![-5.0, 3.0],
+                vec![7.0, -4.0],
+                vec![15.0, 9.0],
+                vec![2.0, 14.0],
+                vec![9.0, 4.0],
+            ],
+        ]
+    }
+
+    #[test]
+    fn benchmark_solvers() {
+        // subtracting squared ranges amplifies the noise of the closed form
+        let solvers: Vec<(&str, Box<dyn Solver>, f64)> = vec![
+            ("nelder-mead", Box::new(NelderMead::default()), 1.5),
+            ("linear", Box::new(LinearLeastSquares), 3.0),
+            ("gauss-newton", Box::new(GaussNewton::default()), 1.5),
+            (
+                "levenberg-marquardt",
+                Box::new(LevenbergMarquardt::default()),
+                1.5,
+            ),
+        ];
+
+        // rmse without and with noise, and the time per solve, of each solver
+        let mut results = Vec::new();
+        for (name, solver, _) in &solvers {
+            let mut rmse = [0.0; 2];
+            let mut runs = 0;
+            let started = Instant::now();
+
+            for (i, noise) in [0.0, 1.0].into_iter().enumerate() {
+                let mut rng = XorShift::new(7);
+                let mut squared_error = 0.0;
+                let mut count = 0;
+
+                for anchors in layouts() {
+                    for _ in 0..20 {
+                        let target = [
+                            5.0 + 5.0 * (2.0 * rng.uniform() - 1.0),
+                            4.0 + 3.0 * (2.0 * rng.uniform() - 1.0),
+                        ];
+                        let ranges: Vec<_> = anchors
+                            .iter()
+                            .map(|a| {
+                                let d = norm(&difference(&target, a)) + noise * rng.gaussian();
+                                Range::new(a.clone(), d.max(0.1), 1.0)
+                            })
+                            .collect();
+                        // seeded like the locator, without knowledge of the target
+                        let start = centroid(&anchors);
+
+                        let solution = solver.solve(&ranges, &start).unwrap();
+                        squared_error += norm(&difference(&solution.position, &target)).powi(2);
+                        count += 1;
+                    }
+                }
+                rmse[i] = (squared_error / count as f64).sqrt();
+                runs += count;
+            }
+            results.push((*name, rmse, started.elapsed() / runs));
+        }
+
+        let report = results
+            .iter()
+            .map(|(name, rmse, time)| {
+                format!(
+                    "{}: rmse {:.3} m exact, {:.3} m noisy, {:?} per solve",
+                    name, rmse[0], rmse[1], time
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        for ((name, rmse, _), (_, _, noisy_bound)) in results.iter().zip(&solvers) {
+            assert!(
+                rmse[0] < 0.05 && rmse[1] < *noisy_bound,
+                "{} is not accurate enough\n{}",
+                name,
+                report
+            );
+        }
+        // the closed form and the analytic Jacobian need far fewer cost evaluations
+        let time = |solver| results.iter().find(|r| r.0 == solver).unwrap().2;
+        for faster in ["linear", "gauss-newton"] {
+            assert!(
+                time(faster) < time("nelder-mead"),
+                "{} is not faster\n{}",
+                faster,
+                report
+            );
+        }
+    }
+
+    #[test]
+    fn test_dilution_of_precision() {
+        let around: Vec<_> = [[10.0, 0.0], [0.0, 10.0], [-10.0, 0.0], [0.0, -10.0]]
+            .iter()
+            .map(|a| Range::new(a.to_vec(), 10.0, 1.0))
+            .collect();
+        let dop = dilution_of_precision(&around, &[0.0, 0.0]).unwrap();
+        assert!((dop - 1.0).abs() < 1e-9);
+
+        let line: Vec<_> = [[-10.0, 0.0], [5.0, 0.0], [20.0, 0.0]]
+            .iter()
+            .map(|a| Range::new(a.to_vec(), 10.0, 1.0))
+            .collect();
+        assert!(dilution_of_precision(&line, &[0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn test_linear_needs_enough_ranges() {
+        let ranges = vec![
+            Range::new(vec![0.0, 0.0], 1.0, 1.0),
+            Range::new(vec![2.0, 0.0], 1.0, 1.0),
+        ];
+        assert!(LinearLeastSquares.solve(&ranges, &[0.0, 0.0]).is_err());
+    }
+}
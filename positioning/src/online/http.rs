@@ -1,9 +1,11 @@
-use crate::beacon::{BeaconId, Output};
+use crate::beacon::{BeaconId, Output, Source};
+use crate::level::Level;
 use crate::signal::Signal;
 use crate::{beacon, geographic};
+use chrono::{DateTime, Utc};
 use embedded_svc::http::client::Client;
 use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
-use log::debug;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -53,6 +55,12 @@ struct ResponseBody {
     #[serde(rename = "heading")]
     pub heading: Option<i32>,
 
+    #[serde(rename = "accuracy")]
+    pub accuracy: Option<f64>,
+
+    #[serde(rename = "timestamp")]
+    pub timestamp: Option<DateTime<Utc>>,
+
     #[serde(flatten)]
     extra_fields: HashMap<String, Value>, // captures unknown fields
 }
@@ -102,6 +110,9 @@ impl HttpClient {
             ("Content-Type", "application/json"),
         ];
 
+        let beacon_count = measurement.len();
+        let newest = measurement.iter().map(|sig| sig.rx_ts).max();
+
         let beacons = measurement
             .iter()
             .map(|sig| BluetoothBeacon {
@@ -148,16 +159,30 @@ impl HttpClient {
         Ok(serde_json::from_slice::<ResponseBody>(&buf).map_or_else(
             |_| Output::default(),
             |res| {
-                Output::new(
+                let floor = Level::parse(&res.indoor.building, &res.indoor.floor)
+                    .inspect_err(|e| warn!("keeping fix on unknown floor: {}", e))
+                    .ok();
+                let output = Output::new(
                     geographic::Position::new(res.location.lat, res.location.lon),
-                    beacon::Room::new(
-                        res.indoor.building.as_str(),
-                        res.indoor.floor.as_str(),
-                        res.indoor.room.as_str(),
-                    ),
+                    beacon::Room {
+                        building: res.indoor.building,
+                        floor,
+                        room: res.indoor.room,
+                    },
                     res.speed,
                     res.heading,
                 )
+                .with_beacons(beacon_count)
+                .with_source(Source::Online);
+
+                let output = match res.accuracy {
+                    Some(accuracy) => output.with_accuracy(accuracy),
+                    None => output,
+                };
+                match res.timestamp.or(newest) {
+                    Some(timestamp) => output.with_timestamp(timestamp),
+                    None => output,
+                }
             },
         ))
     }
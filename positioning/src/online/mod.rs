@@ -2,12 +2,12 @@ mod http;
 
 use crate::beacon::{BeaconId, Output};
 use crate::online::http::HttpClient;
-use crate::signal::Signal;
-use crossbeam_channel::{Receiver, Sender, select};
+use crate::pipeline::{BoundedSender, Stage};
+use crate::signal::Batch;
+use crossbeam_channel::{Receiver, select};
 use log::error;
-use std::thread;
-use std::thread::JoinHandle;
 
+#[derive(Clone)]
 pub struct Locator {
     service_key: String,
     service_client_id: String,
@@ -25,24 +25,21 @@ impl Locator {
 
     pub fn start(
         self,
-        rx: Receiver<Vec<Signal<BeaconId>>>,
-        tx: Sender<Output>,
-    ) -> anyhow::Result<JoinHandle<()>> {
-        let handle = thread::Builder::new()
-            .name("online positioning".to_string())
-            .stack_size(8 * 1024) // 8 KB stack
-            .spawn(move || {
-                let mut positioning = HttpClient::new(
-                    &self.service_endpoint,
-                    &self.service_key,
-                    &self.service_client_id,
-                );
+        rx: Receiver<Batch<BeaconId>>,
+        tx: BoundedSender<Output>,
+    ) -> anyhow::Result<Stage> {
+        Stage::spawn("online positioning", 8 * 1024, move |stop| {
+            let mut positioning = HttpClient::new(
+                &self.service_endpoint,
+                &self.service_key,
+                &self.service_client_id,
+            );
 
-                loop {
-                    select! {
+            loop {
+                select! {
                     recv(rx) -> msg => match msg {
                         Ok(m) => {
-                             match positioning.locate(m) {
+                             match positioning.locate(m.signals) {
                                 Ok(output) => {
                                     if let  Err(e) = tx.send(output){
                                         error!("Failed to send position to service client: {}", e);
@@ -52,11 +49,10 @@ impl Locator {
                             }
                         }
                         Err(_) => break,
-                    }
+                    },
+                    recv(stop) -> _ => break,
                 }
-                }
-            })?;
-
-        Ok(handle)
+            }
+        })
     }
 }
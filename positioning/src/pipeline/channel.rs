@@ -0,0 +1,163 @@
+use crossbeam_channel::{Receiver, SendError, Sender, TrySendError};
+use log::debug;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What to do with a message sent to a full channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Discard the oldest queued message to make room.
+    DropOldest,
+    /// Discard the message being sent.
+    DropNewest,
+    /// Only ever keep the latest message, regardless of the capacity.
+    KeepLatest,
+}
+
+/// Snapshot of the counters of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub depth: usize,
+    pub capacity: usize,
+    pub sent: usize,
+    pub dropped: usize,
+}
+
+impl fmt::Display for ChannelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {}/{}, sent {}, dropped {}",
+            self.depth, self.capacity, self.sent, self.dropped
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+/// Sending half of a bounded channel which never blocks.
+///
+/// A full channel is resolved according to its [`Overflow`] policy. To drop the oldest
+/// message the sender holds a receiver itself, so `send` only fails on disconnect for
+/// [`Overflow::DropNewest`].
+pub struct BoundedSender<T> {
+    tx: Sender<T>,
+    rx: Option<Receiver<T>>,
+    counters: Arc<Counters>,
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+/// Creates a bounded channel resolving overflows with the given policy.
+pub fn channel<T>(capacity: usize, overflow: Overflow) -> (BoundedSender<T>, Receiver<T>) {
+    let capacity = match overflow {
+        Overflow::KeepLatest => 1,
+        Overflow::DropOldest | Overflow::DropNewest => capacity.max(1),
+    };
+    let (tx, rx) = crossbeam_channel::bounded(capacity);
+
+    let sender = BoundedSender {
+        tx,
+        rx: (overflow != Overflow::DropNewest).then(|| rx.clone()),
+        counters: Arc::new(Counters::default()),
+    };
+    (sender, rx)
+}
+
+impl<T> BoundedSender<T> {
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let mut msg = msg;
+        loop {
+            match self.tx.try_send(msg) {
+                Ok(()) => {
+                    self.counters.sent.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(TrySendError::Disconnected(m)) => return Err(SendError(m)),
+                Err(TrySendError::Full(m)) => {
+                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    match &self.rx {
+                        Some(rx) => {
+                            // another receiver may have drained the queue meanwhile
+                            let _ = rx.try_recv();
+                            msg = m;
+                        }
+                        None => {
+                            debug!("channel full, dropping newest message");
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            depth: self.tx.len(),
+            capacity: self.tx.capacity().unwrap_or_default(),
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest() {
+        let (tx, rx) = channel(2, Overflow::DropOldest);
+        for i in 0..4 {
+            tx.send(i).unwrap();
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(tx.stats().dropped, 2);
+        assert_eq!(tx.stats().sent, 4);
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (tx, rx) = channel(2, Overflow::DropNewest);
+        for i in 0..4 {
+            tx.send(i).unwrap();
+        }
+
+        assert_eq!(tx.stats().depth, 2);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(tx.stats().dropped, 2);
+    }
+
+    #[test]
+    fn test_keep_latest() {
+        let (tx, rx) = channel(8, Overflow::KeepLatest);
+        for i in 0..4 {
+            tx.send(i).unwrap();
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn test_send_fails_on_disconnect() {
+        let (tx, rx) = channel(2, Overflow::DropNewest);
+        drop(rx);
+
+        assert!(tx.send(1).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+mod channel;
+mod stage;
+mod supervisor;
+
+pub use channel::{BoundedSender, ChannelStats, Overflow, channel};
+pub use stage::{Stage, StopHandle};
+pub use supervisor::Supervisor;
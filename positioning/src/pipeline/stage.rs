@@ -0,0 +1,77 @@
+use crossbeam_channel::{Receiver, Sender, bounded};
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Requests a [`Stage`] to stop. Cheap to clone and safe to call more than once.
+#[derive(Debug, Clone)]
+pub struct StopHandle {
+    tx: Sender<()>,
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        // a full channel means a stop is already pending
+        let _ = self.tx.try_send(());
+    }
+}
+
+/// A running pipeline stage: its thread and the channel used to cancel it.
+///
+/// The thread receives the cancellation [`Receiver`] and is expected to return once it
+/// yields anything, either a stop request or a disconnect. Dropping the `Stage` without
+/// stopping or joining it therefore stops the thread as well.
+///
+/// On a stop request a stage finishes the message in hand and flushes what it holds,
+/// while queued input stays in the channel for a restarted stage. When its input
+/// channel disconnects, a stage handles everything still queued and returns.
+pub struct Stage {
+    name: String,
+    stop: StopHandle,
+    handle: JoinHandle<()>,
+}
+
+impl Stage {
+    pub fn spawn<F>(name: &str, stack_size: usize, f: F) -> anyhow::Result<Self>
+    where
+        F: FnOnce(Receiver<()>) + Send + 'static,
+    {
+        let (tx, rx) = bounded(1);
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .stack_size(stack_size)
+            .spawn(move || f(rx))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            stop: StopHandle { tx },
+            handle,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Requests the stage to stop and waits until it has drained.
+    pub fn stop(self) -> thread::Result<()> {
+        self.stop.stop();
+        self.handle.join()
+    }
+
+    /// Waits for the stage to finish on its own; returns `Err` if it panicked.
+    pub fn join(self) -> thread::Result<()> {
+        // keep the stop channel connected, dropping it would cancel the stage
+        let Self { stop, handle, .. } = self;
+        let result = handle.join();
+        drop(stop);
+        result
+    }
+}
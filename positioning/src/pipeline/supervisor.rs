@@ -0,0 +1,168 @@
+use crate::pipeline::Stage;
+use crossbeam_channel::{RecvTimeoutError, select};
+use log::{error, info, warn};
+use std::time::Duration;
+
+/// Restarts a stage whenever its thread panics.
+///
+/// The supervised stage is created by a factory, so every restart starts from a fresh
+/// configuration. A stage returning normally is not restarted.
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    max_restarts: usize,
+    backoff: Duration,
+    poll_interval: Duration,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            backoff: Duration::from_secs(1),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Supervisor {
+    /// Number of restarts after which the supervisor gives up.
+    pub fn max_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Delay before a panicked stage is started again.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// How often the supervised stage is checked.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Starts the stage and supervises it from a separate thread.
+    ///
+    /// The returned stage represents the supervisor; stopping it stops the supervised
+    /// stage as well.
+    pub fn supervise<F>(self, name: &str, mut start: F) -> anyhow::Result<Stage>
+    where
+        F: FnMut() -> anyhow::Result<Stage> + Send + 'static,
+    {
+        let mut stage = start()?;
+        let supervised = stage.name().to_string();
+
+        Stage::spawn(name, 8 * 1024, move |stop| {
+            let mut restarts = 0;
+
+            loop {
+                select! {
+                    recv(stop) -> _ => {
+                        info!("stopping {}", supervised);
+                        if stage.stop().is_err() {
+                            error!("{} panicked while stopping", supervised);
+                        }
+                        return;
+                    },
+                    default(self.poll_interval) => {}
+                }
+
+                if !stage.is_finished() {
+                    continue;
+                }
+
+                if stage.join().is_ok() {
+                    info!("{} finished", supervised);
+                    return;
+                }
+
+                restarts += 1;
+                if restarts > self.max_restarts {
+                    error!("{} panicked too often, giving up", supervised);
+                    return;
+                }
+
+                warn!(
+                    "{} panicked, restart {}/{}",
+                    supervised, restarts, self.max_restarts
+                );
+                if let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(self.backoff) {
+                    match start() {
+                        Ok(restarted) => stage = restarted,
+                        Err(e) => {
+                            error!("cannot restart {}: {:?}", supervised, e);
+                            return;
+                        }
+                    }
+                } else {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn supervisor() -> Supervisor {
+        Supervisor::default()
+            .backoff(Duration::from_millis(1))
+            .poll_interval(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_restarts_panicked_stage() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let counter = starts.clone();
+
+        let stage = supervisor()
+            .supervise("supervisor", move || {
+                let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                Stage::spawn("flaky", 64 * 1024, move |_stop| {
+                    if attempt < 2 {
+                        panic!("attempt {}", attempt);
+                    }
+                })
+            })
+            .unwrap();
+
+        assert!(stage.join().is_ok());
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_restarts() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let counter = starts.clone();
+
+        let stage = supervisor()
+            .max_restarts(2)
+            .supervise("supervisor", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Stage::spawn("broken", 64 * 1024, |_stop| panic!("always"))
+            })
+            .unwrap();
+
+        assert!(stage.join().is_ok());
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_stop_reaches_supervised_stage() {
+        let stage = supervisor()
+            .supervise("supervisor", || {
+                Stage::spawn("worker", 64 * 1024, |stop| {
+                    let _ = stop.recv();
+                })
+            })
+            .unwrap();
+
+        assert!(stage.stop().is_ok());
+    }
+}
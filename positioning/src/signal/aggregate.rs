@@ -0,0 +1,361 @@
+use crate::signal::kalman::MEASUREMENT_NOISE;
+use crate::signal::{KalmanFilter, Signal};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// Collapses the signals of a window into one signal per beacon.
+///
+/// The returned signal of a beacon is its latest one, with `rssi` replaced by the
+/// aggregated value and `variance` set to the variance of that value, so signals
+/// aggregated differently or from more samples can be weighed against each other.
+pub trait Aggregator<T>: Send {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>>;
+}
+
+/// Arithmetic mean of the RSSI.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mean;
+
+impl<T: Clone + PartialEq + Send> Aggregator<T> for Mean {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        group_by_beacon(window)
+            .into_iter()
+            .map(|group| {
+                let rssi = rssi_values(&group);
+                let variance = sample_variance(&rssi) / rssi.len() as f64;
+                collapse(&group, mean(&rssi), variance)
+            })
+            .collect()
+    }
+}
+
+/// Median of the RSSI, robust against single outliers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Median;
+
+impl<T: Clone + PartialEq + Send> Aggregator<T> for Median {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        group_by_beacon(window)
+            .into_iter()
+            .map(|group| {
+                let mut rssi = rssi_values(&group);
+                rssi.sort_by(f64::total_cmp);
+                // the median of normal samples varies π/2 times as much as their mean
+                let variance = PI / 2.0 * sample_variance(&rssi) / rssi.len() as f64;
+                collapse(&group, median(&rssi), variance)
+            })
+            .collect()
+    }
+}
+
+/// Strongest RSSI of the window; fading only ever attenuates a signal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxRssi;
+
+impl<T: Clone + PartialEq + Send> Aggregator<T> for MaxRssi {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        group_by_beacon(window)
+            .into_iter()
+            .map(|group| {
+                let rssi = rssi_values(&group);
+                // the maximum is a single sample, varying as much as any other
+                let max = rssi.iter().copied().fold(f64::MIN, f64::max);
+                collapse(&group, max, sample_variance(&rssi))
+            })
+            .collect()
+    }
+}
+
+/// Mean of the RSSI after dropping the given fraction of samples at both ends.
+#[derive(Debug, Clone, Copy)]
+pub struct TrimmedMean {
+    trim: f64,
+}
+
+impl TrimmedMean {
+    /// `trim` is the fraction removed from each end, clamped to `[0, 0.5)`.
+    pub fn new(trim: f64) -> Self {
+        Self {
+            trim: trim.clamp(0.0, 0.49),
+        }
+    }
+}
+
+impl Default for TrimmedMean {
+    fn default() -> Self {
+        Self::new(0.2)
+    }
+}
+
+impl<T: Clone + PartialEq + Send> Aggregator<T> for TrimmedMean {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        group_by_beacon(window)
+            .into_iter()
+            .map(|group| {
+                let mut rssi = rssi_values(&group);
+                rssi.sort_by(f64::total_cmp);
+                let cut = (rssi.len() as f64 * self.trim).floor() as usize;
+                let kept = &rssi[cut..rssi.len() - cut];
+                let variance = sample_variance(kept) / kept.len() as f64;
+                collapse(&group, mean(kept), variance)
+            })
+            .collect()
+    }
+}
+
+/// Mean of the RSSI weighted by age, halving the weight every `half_life`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeDecayed {
+    half_life: Duration,
+}
+
+impl TimeDecayed {
+    pub fn new(half_life: Duration) -> Self {
+        Self { half_life }
+    }
+}
+
+impl Default for TimeDecayed {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(2))
+    }
+}
+
+impl<T: Clone + PartialEq + Send> Aggregator<T> for TimeDecayed {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        let half_life = self.half_life.as_secs_f64().max(0.001);
+
+        group_by_beacon(window)
+            .into_iter()
+            .map(|group| {
+                let newest = group
+                    .iter()
+                    .map(|s| s.rx_monotonic)
+                    .max()
+                    .unwrap_or_default();
+                let weights: Vec<f64> = group
+                    .iter()
+                    .map(|s| {
+                        let age = newest.saturating_sub(s.rx_monotonic).as_secs_f64();
+                        0.5f64.powf(age / half_life)
+                    })
+                    .collect();
+                let total: f64 = weights.iter().sum();
+                let sum: f64 = weights
+                    .iter()
+                    .zip(&group)
+                    .map(|(w, s)| w * s.rssi as f64)
+                    .sum();
+                // a weighted mean counts as total² / Σw² samples
+                let squares: f64 = weights.iter().map(|w| w * w).sum();
+                let variance = sample_variance(&rssi_values(&group)) * squares / (total * total);
+                collapse(&group, sum / total, variance)
+            })
+            .collect()
+    }
+}
+
+/// Runs a [`KalmanFilter`] per beacon across consecutive windows.
+///
+/// Windows may overlap, so only signals newer than the last one fed into a filter are
+/// used. Filters of beacons missing from a window are dropped.
+#[derive(Debug, Clone)]
+pub struct Kalman<T> {
+    filters: HashMap<T, KalmanFilter>,
+}
+
+impl<T> Default for Kalman<T> {
+    fn default() -> Self {
+        Self {
+            filters: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash + Send> Aggregator<T> for Kalman<T> {
+    fn aggregate(&mut self, window: &[Signal<T>]) -> Vec<Signal<T>> {
+        let groups = group_by_beacon(window);
+
+        self.filters
+            .retain(|beacon, _| groups.iter().any(|g| &g[0].beacon == beacon));
+
+        groups
+            .into_iter()
+            .map(|mut group| {
+                group.sort_by_key(|s| s.rx_monotonic);
+
+                let first = group[0];
+                let filter = self
+                    .filters
+                    .entry(first.beacon.clone())
+                    .or_insert_with(|| KalmanFilter::new(first.rssi as f64, first.rx_monotonic));
+
+                let watermark = filter.updated();
+                for signal in group.iter().filter(|s| s.rx_monotonic > watermark) {
+                    filter.update(signal.rssi as f64, signal.rx_monotonic);
+                }
+
+                let latest = group[group.len() - 1];
+                Signal {
+                    rssi: to_rssi(filter.estimate()),
+                    ..latest.clone()
+                }
+                .with_variance(filter.variance())
+            })
+            .collect()
+    }
+}
+
+/// Groups the window by beacon, in order of first appearance.
+fn group_by_beacon<T: PartialEq>(window: &[Signal<T>]) -> Vec<Vec<&Signal<T>>> {
+    let mut groups: Vec<Vec<&Signal<T>>> = Vec::new();
+    for signal in window {
+        match groups.iter_mut().find(|g| g[0].beacon == signal.beacon) {
+            Some(group) => group.push(signal),
+            None => groups.push(vec![signal]),
+        }
+    }
+    groups
+}
+
+fn rssi_values<T>(group: &[&Signal<T>]) -> Vec<f64> {
+    group.iter().map(|s| s.rssi as f64).collect()
+}
+
+/// Returns the latest signal of the group carrying the aggregated RSSI and its variance.
+fn collapse<T: Clone>(group: &[&Signal<T>], rssi: f64, variance: f64) -> Signal<T> {
+    let latest = group
+        .iter()
+        .max_by_key(|s| s.rx_monotonic)
+        .expect("groups are never empty");
+    Signal {
+        rssi: to_rssi(rssi),
+        ..(*latest).clone()
+    }
+    .with_variance(variance)
+}
+
+/// Spread of single samples in dBm², that of an advertisement if there is only one.
+fn sample_variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return MEASUREMENT_NOISE;
+    }
+    let mean = mean(values);
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+pub(super) fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+fn to_rssi(value: f64) -> i8 {
+    value.round().clamp(i8::MIN as f64, i8::MAX as f64) as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn window() -> Vec<Signal<&'static str>> {
+        let clock = ManualClock::default();
+        let mut window: Vec<_> = [-69, -71, -40, -72, -70]
+            .iter()
+            .map(|rssi| {
+                clock.advance(Duration::from_secs(1));
+                Signal::new("a", -59, *rssi, &clock)
+            })
+            .collect();
+        window.push(Signal::new("b", -59, -80, &clock));
+        window.reverse();
+        window
+    }
+
+    #[test]
+    fn test_one_signal_per_beacon() {
+        let window = window();
+        assert_eq!(Mean.aggregate(&window).len(), 2);
+        assert_eq!(Median.aggregate(&window).len(), 2);
+        assert_eq!(MaxRssi.aggregate(&window).len(), 2);
+        assert_eq!(TrimmedMean::default().aggregate(&window).len(), 2);
+        assert_eq!(TimeDecayed::default().aggregate(&window).len(), 2);
+        assert_eq!(Kalman::default().aggregate(&window).len(), 2);
+    }
+
+    #[test]
+    fn test_aggregated_rssi() {
+        let window = window();
+        assert_eq!(Mean.aggregate(&window)[1].rssi, -64);
+        assert_eq!(Median.aggregate(&window)[1].rssi, -70);
+        assert_eq!(MaxRssi.aggregate(&window)[1].rssi, -40);
+        assert_eq!(TrimmedMean::default().aggregate(&window)[1].rssi, -70);
+    }
+
+    #[test]
+    fn test_variance_shrinks_with_sample_count() {
+        let clock = ManualClock::default();
+        let mut window = Vec::new();
+        for i in 0..8 {
+            clock.advance(Duration::from_secs(1));
+            let rssi = if i % 2 == 0 { -68 } else { -72 };
+            window.push(Signal::new("often", -59, rssi, &clock));
+            if i >= 6 {
+                window.push(Signal::new("rarely", -59, rssi, &clock));
+            }
+        }
+
+        let aggregators: Vec<(&str, Box<dyn Aggregator<&str>>)> = vec![
+            ("mean", Box::new(Mean)),
+            ("median", Box::new(Median)),
+            ("trimmed mean", Box::new(TrimmedMean::default())),
+            ("time decayed", Box::new(TimeDecayed::default())),
+            ("kalman", Box::new(Kalman::default())),
+        ];
+        for (name, mut aggregator) in aggregators {
+            let signals = aggregator.aggregate(&window);
+            let variance = |beacon| {
+                let signal = signals.iter().find(|s| s.beacon == beacon).unwrap();
+                signal.variance.unwrap()
+            };
+            assert!(
+                variance("often") < variance("rarely"),
+                "{}: {} vs {}",
+                name,
+                variance("often"),
+                variance("rarely")
+            );
+        }
+    }
+
+    #[test]
+    fn test_time_decayed_prefers_recent() {
+        let clock = ManualClock::default();
+        let old = Signal::new("a", -59, -90, &clock);
+        clock.advance(Duration::from_secs(10));
+        let window = vec![Signal::new("a", -59, -60, &clock), old];
+
+        assert!(TimeDecayed::default().aggregate(&window)[0].rssi > -62);
+    }
+
+    #[test]
+    fn test_kalman_ignores_overlapping_signals() {
+        let window = window();
+        let mut kalman = Kalman::default();
+        let first = kalman.aggregate(&window);
+        let second = kalman.aggregate(&window);
+
+        assert_eq!(first[1].rssi, second[1].rssi);
+        assert_eq!(first[1].variance, second[1].variance);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::clock::Clock;
+use crate::signal::Signal;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Statistics over the raw RSSI of one beacon within a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalStats {
+    pub count: usize,
+    pub min: i8,
+    pub max: i8,
+    pub stddev: f64,
+    /// Monotonic time of the latest signal.
+    pub last_seen: Duration,
+    /// Number of samples dropped as outliers since the beacon was first heard.
+    pub rejected: usize,
+}
+
+impl SignalStats {
+    fn from_signals<'a, T: 'a>(signals: impl Iterator<Item = &'a Signal<T>>) -> Option<Self> {
+        let mut count = 0usize;
+        let mut min = i8::MAX;
+        let mut max = i8::MIN;
+        let mut sum = 0.0;
+        let mut sum_sq = 0.0;
+        let mut last_seen = Duration::ZERO;
+
+        for s in signals {
+            count += 1;
+            min = min.min(s.rssi);
+            max = max.max(s.rssi);
+            sum += s.rssi as f64;
+            sum_sq += (s.rssi as f64).powi(2);
+            last_seen = last_seen.max(s.rx_monotonic);
+        }
+
+        if count == 0 {
+            return None;
+        }
+
+        let mean = sum / count as f64;
+        let variance = (sum_sq / count as f64 - mean * mean).max(0.0);
+
+        Some(Self {
+            count,
+            min,
+            max,
+            stddev: variance.sqrt(),
+            last_seen,
+            rejected: 0,
+        })
+    }
+}
+
+/// Keeps a bounded history of signals per beacon.
+///
+/// Every beacon keeps at most `per_beacon` signals. Once `max_size` signals are stored in
+/// total, the oldest signal of the beacon with the longest history is evicted, so a
+/// single chatty beacon cannot push the others out of the window.
+pub struct Buffer<T: Clone + Eq + Hash> {
+    beacons: HashMap<T, VecDeque<Signal<T>>>,
+    per_beacon: usize,
+    max_size: usize,
+    len: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T: Clone + Eq + Hash> Buffer<T> {
+    pub fn new(per_beacon: usize, max_size: usize, clock: Arc<dyn Clock>) -> Self {
+        Buffer {
+            beacons: HashMap::new(),
+            per_beacon: per_beacon.max(1),
+            max_size: max_size.max(1),
+            len: 0,
+            clock,
+        }
+    }
+
+    pub fn push(&mut self, signal: Signal<T>) {
+        let history = self.beacons.entry(signal.beacon.clone()).or_default();
+        if history.len() >= self.per_beacon {
+            history.pop_back();
+            self.len -= 1;
+        }
+        history.push_front(signal);
+        self.len += 1;
+
+        while self.len > self.max_size {
+            self.evict();
+        }
+    }
+
+    /// Removes the oldest signal of the beacon with the longest history.
+    fn evict(&mut self) {
+        let victim = self
+            .beacons
+            .iter()
+            .max_by(|(_, a), (_, b)| {
+                a.len().cmp(&b.len()).then_with(|| {
+                    let oldest = |h: &VecDeque<Signal<T>>| h.back().map(|s| s.rx_monotonic);
+                    oldest(b).cmp(&oldest(a))
+                })
+            })
+            .map(|(beacon, _)| beacon.clone());
+
+        if let Some(beacon) = victim {
+            self.remove_oldest(&beacon);
+        }
+    }
+
+    fn remove_oldest(&mut self, beacon: &T) {
+        if let Some(history) = self.beacons.get_mut(beacon) {
+            if history.pop_back().is_some() {
+                self.len -= 1;
+            }
+            if history.is_empty() {
+                self.beacons.remove(beacon);
+            }
+        }
+    }
+
+    /// Drops all signals older than the given window.
+    pub fn expire(&mut self, window: Duration) {
+        let recent = self.recent(window);
+        self.beacons.retain(|_, history| {
+            while history.back().is_some_and(|s| !recent(s)) {
+                history.pop_back();
+            }
+            !history.is_empty()
+        });
+        self.len = self.beacons.values().map(VecDeque::len).sum();
+    }
+
+    /// Returns the signals received within the given window, newest first.
+    pub fn get_recent_signals(&self, window: Duration) -> Vec<Signal<T>> {
+        let recent = self.recent(window);
+        let mut signals = self
+            .beacons
+            .values()
+            .flat_map(|history| history.iter().take_while(|s| recent(s)))
+            .cloned()
+            .collect::<Vec<Signal<T>>>();
+        signals.sort_by_key(|s| std::cmp::Reverse(s.rx_monotonic));
+        signals
+    }
+
+    /// Returns the statistics of a beacon's signals received within the given window.
+    pub fn stats(&self, beacon: &T, window: Duration) -> Option<SignalStats> {
+        let recent = self.recent(window);
+        self.beacons
+            .get(beacon)
+            .and_then(|history| SignalStats::from_signals(history.iter().take_while(|s| recent(s))))
+    }
+
+    /// Returns the history of a beacon, newest first.
+    pub fn history(&self, beacon: &T) -> impl Iterator<Item = &Signal<T>> {
+        self.beacons.get(beacon).into_iter().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a predicate telling whether a signal was received within the window.
+    fn recent(&self, window: Duration) -> impl Fn(&Signal<T>) -> bool + use<T> {
+        let since = self.clock.monotonic().checked_sub(window);
+        move |s| since.is_none_or(|since| s.rx_monotonic > since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    const WINDOW: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_chatty_beacon_does_not_evict_others() {
+        let clock = ManualClock::default();
+        let mut buffer = Buffer::new(8, 10, Arc::new(clock.clone()));
+        buffer.push(Signal::new("quiet", -59, -80, &clock));
+        for _ in 0..50 {
+            clock.advance(Duration::from_millis(100));
+            buffer.push(Signal::new("chatty", -59, -60, &clock));
+        }
+
+        assert_eq!(buffer.len(), 9);
+        assert_eq!(buffer.history(&"quiet").count(), 1);
+        assert_eq!(buffer.history(&"chatty").count(), 8);
+    }
+
+    #[test]
+    fn test_total_capacity_is_shared_fairly() {
+        let clock = ManualClock::default();
+        let mut buffer = Buffer::new(8, 6, Arc::new(clock.clone()));
+        for _ in 0..4 {
+            for beacon in ["a", "b", "c"] {
+                clock.advance(Duration::from_millis(100));
+                buffer.push(Signal::new(beacon, -59, -60, &clock));
+            }
+        }
+
+        assert_eq!(buffer.len(), 6);
+        for beacon in ["a", "b", "c"] {
+            assert_eq!(buffer.history(&beacon).count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        let clock = ManualClock::default();
+        let mut buffer = Buffer::new(8, 20, Arc::new(clock.clone()));
+        buffer.push(Signal::new("a", -59, -70, &clock));
+        clock.advance(Duration::from_secs(4));
+        buffer.push(Signal::new("b", -59, -70, &clock));
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(buffer.get_recent_signals(WINDOW).len(), 1);
+        buffer.expire(WINDOW);
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.history(&"a").count(), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let clock = ManualClock::default();
+        let mut buffer = Buffer::new(8, 20, Arc::new(clock.clone()));
+        for rssi in [-70, -60, -80] {
+            clock.advance(Duration::from_millis(100));
+            buffer.push(Signal::new("a", -59, rssi, &clock));
+        }
+
+        let stats = buffer.stats(&"a", WINDOW).unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, -80);
+        assert_eq!(stats.max, -60);
+        assert!((stats.stddev - 8.1649658).abs() < 1e-6);
+        assert_eq!(stats.last_seen, Duration::from_millis(300));
+        assert!(buffer.stats(&"b", WINDOW).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+/// Process noise in dBm² per second: how much the true RSSI of a beacon is
+/// expected to drift while the tracker moves.
+const PROCESS_NOISE: f64 = 0.5;
+
+/// Measurement noise in dBm² of a single advertisement (σ ≈ 4 dB).
+pub(super) const MEASUREMENT_NOISE: f64 = 16.0;
+
+/// One-dimensional Kalman filter tracking the RSSI of a single beacon.
+#[derive(Debug, Clone)]
+pub struct KalmanFilter {
+    estimate: f64,
+    variance: f64,
+    process_noise: f64,
+    measurement_noise: f64,
+    updated: Duration,
+}
+
+impl KalmanFilter {
+    /// Initialises the filter from the first measurement of a beacon.
+    pub fn new(rssi: f64, ts: Duration) -> Self {
+        Self::with_noise(rssi, ts, PROCESS_NOISE, MEASUREMENT_NOISE)
+    }
+
+    pub fn with_noise(rssi: f64, ts: Duration, process_noise: f64, measurement_noise: f64) -> Self {
+        Self {
+            estimate: rssi,
+            variance: measurement_noise,
+            process_noise,
+            measurement_noise,
+            updated: ts,
+        }
+    }
+
+    /// Feeds a measurement taken at monotonic time `ts` into the filter and returns the new estimate.
+    pub fn update(&mut self, rssi: f64, ts: Duration) -> f64 {
+        // predict: the longer the beacon was silent, the less we trust the old estimate
+        let elapsed = ts.saturating_sub(self.updated).as_secs_f64();
+        self.variance += self.process_noise * elapsed;
+
+        // correct
+        let gain = self.variance / (self.variance + self.measurement_noise);
+        self.estimate += gain * (rssi - self.estimate);
+        self.variance *= 1.0 - gain;
+        self.updated = self.updated.max(ts);
+
+        self.estimate
+    }
+
+    pub fn estimate(&self) -> f64 {
+        self.estimate
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.variance
+    }
+
+    /// Timestamp of the latest measurement fed into the filter.
+    pub fn updated(&self) -> Duration {
+        self.updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooths_spike() {
+        let t0 = Duration::ZERO;
+        let mut kalman = KalmanFilter::new(-70.0, t0);
+        for i in 1..10 {
+            kalman.update(-70.0, t0 + Duration::from_millis(100 * i));
+        }
+        let estimate = kalman.update(-55.0, t0 + Duration::from_secs(1));
+
+        assert!(estimate < -67.0, "estimate {} follows the spike", estimate);
+    }
+
+    #[test]
+    fn test_variance_shrinks_with_measurements() {
+        let t0 = Duration::ZERO;
+        let mut kalman = KalmanFilter::new(-70.0, t0);
+        let initial = kalman.variance();
+        kalman.update(-71.0, t0 + Duration::from_millis(100));
+        kalman.update(-69.0, t0 + Duration::from_millis(200));
+
+        assert!(kalman.variance() < initial);
+    }
+}
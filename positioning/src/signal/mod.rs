@@ -0,0 +1,99 @@
+mod aggregate;
+mod buffer;
+mod kalman;
+mod motion;
+mod outlier;
+mod processor;
+
+pub use aggregate::{Aggregator, Kalman, MaxRssi, Mean, Median, TimeDecayed, TrimmedMean};
+pub use buffer::{Buffer, SignalStats};
+pub use kalman::KalmanFilter;
+pub use motion::{Motion, MotionDetector};
+pub use outlier::Hampel;
+pub use processor::{Emission, Processor, ProcessorConfig};
+
+use crate::clock::Clock;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Signal<T> {
+    pub beacon: T,
+    pub tx_power: i8,
+    pub rssi: i8,
+    pub rx_ts: DateTime<Utc>,
+    /// Monotonic receive time, see [`Clock::monotonic`]. Used for all age computations.
+    pub rx_monotonic: Duration,
+    pub distance: Option<f64>,
+    /// Variance in dBm² of `rssi` as an estimate of the beacon's RSSI, set once the
+    /// signal has been aggregated. It is that of the aggregated value, not the spread
+    /// of single advertisements, so it shrinks as more of them are aggregated.
+    pub variance: Option<f64>,
+    /// Statistics of the raw signals the emitted signal was aggregated from.
+    pub stats: Option<SignalStats>,
+}
+
+impl<T> Signal<T> {
+    pub fn with_distance(self, distance: f64) -> Signal<T> {
+        Self {
+            distance: Some(distance),
+            ..self
+        }
+    }
+
+    pub fn with_variance(self, variance: f64) -> Signal<T> {
+        Self {
+            variance: Some(variance),
+            ..self
+        }
+    }
+
+    pub fn with_stats(self, stats: SignalStats) -> Signal<T> {
+        Self {
+            stats: Some(stats),
+            ..self
+        }
+    }
+
+    /// Replaces the beacon, keeping timestamps and everything derived so far.
+    pub fn map_beacon<U>(self, f: impl FnOnce(T) -> U) -> Signal<U> {
+        Signal {
+            beacon: f(self.beacon),
+            tx_power: self.tx_power,
+            rssi: self.rssi,
+            rx_ts: self.rx_ts,
+            rx_monotonic: self.rx_monotonic,
+            distance: self.distance,
+            variance: self.variance,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T: Clone> Signal<T> {
+    pub fn new(beacon: T, tx_power: i8, rssi: i8, clock: &dyn Clock) -> Self {
+        Signal {
+            beacon,
+            tx_power,
+            rssi,
+            rx_ts: clock.now(),
+            rx_monotonic: clock.monotonic(),
+            distance: None,
+            variance: None,
+            stats: None,
+        }
+    }
+}
+
+/// One aggregated signal per beacon, as emitted by the [`Processor`] for a window.
+#[derive(Debug, Clone)]
+pub struct Batch<T> {
+    pub signals: Vec<Signal<T>>,
+    pub motion: Motion,
+}
+
+impl<T> Batch<T> {
+    pub fn new(signals: Vec<Signal<T>>, motion: Motion) -> Self {
+        Self { signals, motion }
+    }
+}
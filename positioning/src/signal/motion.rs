@@ -0,0 +1,163 @@
+use crate::signal::Signal;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Whether the tracker is carried around or lying still.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Motion {
+    #[default]
+    Unknown,
+    Stationary,
+    Moving,
+}
+
+/// Classifies motion by comparing consecutive emitted windows.
+///
+/// Walking changes which beacons are heard, shifts their RSSI between windows and
+/// increases the spread within a window; a tracker lying on a desk does none of that.
+/// The state only changes after `confirmations` consecutive windows agree.
+#[derive(Debug, Clone)]
+pub struct MotionDetector<T> {
+    /// Fraction of the beacon set that may change between windows.
+    max_set_change: f64,
+    /// Mean absolute RSSI change in dB of beacons heard in both windows.
+    max_rssi_change: f64,
+    /// Mean standard deviation in dB of the RSSI within a window.
+    max_stddev: f64,
+    confirmations: usize,
+    previous: HashMap<T, f64>,
+    candidate: Motion,
+    agreeing: usize,
+    state: Motion,
+}
+
+impl<T> Default for MotionDetector<T> {
+    fn default() -> Self {
+        Self {
+            max_set_change: 0.4,
+            max_rssi_change: 4.0,
+            max_stddev: 5.0,
+            confirmations: 2,
+            previous: HashMap::new(),
+            candidate: Motion::Unknown,
+            agreeing: 0,
+            state: Motion::Unknown,
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> MotionDetector<T> {
+    /// Feeds the aggregated signals of a window and returns the motion state.
+    pub fn update(&mut self, signals: &[Signal<T>]) -> Motion {
+        let current: HashMap<T, f64> = signals
+            .iter()
+            .map(|s| (s.beacon.clone(), s.rssi as f64))
+            .collect();
+
+        let observed = self.classify(signals, &current);
+        self.previous = current;
+
+        if observed == self.candidate {
+            self.agreeing += 1;
+        } else {
+            self.candidate = observed;
+            self.agreeing = 1;
+        }
+
+        if self.agreeing >= self.confirmations || self.state == Motion::Unknown {
+            self.state = self.candidate;
+        }
+        self.state
+    }
+
+    fn classify(&self, signals: &[Signal<T>], current: &HashMap<T, f64>) -> Motion {
+        if self.previous.is_empty() || current.is_empty() {
+            return Motion::Unknown;
+        }
+
+        let common: Vec<(f64, f64)> = current
+            .iter()
+            .filter_map(|(beacon, rssi)| self.previous.get(beacon).map(|p| (*p, *rssi)))
+            .collect();
+        let union = current.len() + self.previous.len() - common.len();
+        let set_change = 1.0 - common.len() as f64 / union as f64;
+
+        let rssi_change = if common.is_empty() {
+            f64::INFINITY
+        } else {
+            common.iter().map(|(p, c)| (c - p).abs()).sum::<f64>() / common.len() as f64
+        };
+
+        let deviations: Vec<f64> = signals
+            .iter()
+            .filter_map(|s| s.stats.as_ref().map(|stats| stats.stddev))
+            .collect();
+        let stddev = if deviations.is_empty() {
+            0.0
+        } else {
+            deviations.iter().sum::<f64>() / deviations.len() as f64
+        };
+
+        if set_change > self.max_set_change
+            || rssi_change > self.max_rssi_change
+            || stddev > self.max_stddev
+        {
+            Motion::Moving
+        } else {
+            Motion::Stationary
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn window(clock: &ManualClock, beacons: &[(&'static str, i8)]) -> Vec<Signal<&'static str>> {
+        beacons
+            .iter()
+            .map(|(b, rssi)| Signal::new(*b, -59, *rssi, clock))
+            .collect()
+    }
+
+    #[test]
+    fn test_stationary() {
+        let clock = ManualClock::default();
+        let mut detector = MotionDetector::default();
+        let beacons = [("a", -70), ("b", -75), ("c", -80)];
+
+        assert_eq!(detector.update(&window(&clock, &beacons)), Motion::Unknown);
+        assert_eq!(
+            detector.update(&window(&clock, &beacons)),
+            Motion::Stationary
+        );
+        assert_eq!(
+            detector.update(&window(&clock, &beacons)),
+            Motion::Stationary
+        );
+    }
+
+    #[test]
+    fn test_moving_needs_confirmation() {
+        let clock = ManualClock::default();
+        let mut detector = MotionDetector::default();
+        let beacons = [("a", -70), ("b", -75), ("c", -80)];
+        for _ in 0..3 {
+            detector.update(&window(&clock, &beacons));
+        }
+
+        let walking = [
+            [("c", -70), ("d", -72), ("e", -78)],
+            [("e", -65), ("f", -70), ("g", -80)],
+        ];
+        assert_eq!(
+            detector.update(&window(&clock, &walking[0])),
+            Motion::Stationary
+        );
+        assert_eq!(
+            detector.update(&window(&clock, &walking[1])),
+            Motion::Moving
+        );
+    }
+}
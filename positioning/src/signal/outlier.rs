@@ -0,0 +1,147 @@
+use crate::signal::Signal;
+use crate::signal::aggregate::median;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Scale factor turning the median absolute deviation into a standard deviation
+/// estimate for normally distributed samples.
+const MAD_SCALE: f64 = 1.4826;
+
+/// Hampel filter rejecting RSSI samples that deviate too far from the median of the
+/// beacon's recent samples.
+///
+/// A sample is rejected if it is more than `threshold` robust standard deviations
+/// (`1.4826 * MAD`) away from the median. The median is taken over the raw samples,
+/// rejected ones included, so a genuine change in signal strength is accepted once it
+/// dominates the window.
+#[derive(Debug, Clone)]
+pub struct Hampel<T> {
+    window: usize,
+    threshold: f64,
+    min_samples: usize,
+    min_deviation: f64,
+    samples: HashMap<T, VecDeque<i8>>,
+    rejected: HashMap<T, usize>,
+    total_rejected: usize,
+}
+
+impl<T> Default for Hampel<T> {
+    fn default() -> Self {
+        Self::new(9, 3.0)
+    }
+}
+
+impl<T> Hampel<T> {
+    pub fn new(window: usize, threshold: f64) -> Self {
+        Self {
+            window: window.max(3),
+            threshold,
+            min_samples: 5,
+            min_deviation: 1.0,
+            samples: HashMap::new(),
+            rejected: HashMap::new(),
+            total_rejected: 0,
+        }
+    }
+
+    /// Number of samples needed before anything is rejected.
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Lower bound of the robust standard deviation in dB, so that a beacon with an
+    /// almost constant RSSI does not reject every 1 dB fluctuation.
+    pub fn min_deviation(mut self, min_deviation: f64) -> Self {
+        self.min_deviation = min_deviation;
+        self
+    }
+
+    /// Total number of rejected samples since the filter was created.
+    pub fn rejected(&self) -> usize {
+        self.total_rejected
+    }
+}
+
+impl<T: Clone + Eq + Hash> Hampel<T> {
+    /// Records the sample and returns whether it is plausible.
+    pub fn accept(&mut self, signal: &Signal<T>) -> bool {
+        let samples = self.samples.entry(signal.beacon.clone()).or_default();
+
+        let accepted = samples.len() < self.min_samples || {
+            let mut sorted: Vec<f64> = samples.iter().map(|&r| r as f64).collect();
+            sorted.sort_by(f64::total_cmp);
+            let center = median(&sorted);
+
+            let mut deviations: Vec<f64> = sorted.iter().map(|r| (r - center).abs()).collect();
+            deviations.sort_by(f64::total_cmp);
+            let sigma = (MAD_SCALE * median(&deviations)).max(self.min_deviation);
+
+            (signal.rssi as f64 - center).abs() <= self.threshold * sigma
+        };
+
+        if samples.len() >= self.window {
+            samples.pop_back();
+        }
+        samples.push_front(signal.rssi);
+
+        if !accepted {
+            *self.rejected.entry(signal.beacon.clone()).or_default() += 1;
+            self.total_rejected += 1;
+        }
+        accepted
+    }
+
+    /// Number of rejected samples of a beacon since the filter was created.
+    pub fn rejected_by(&self, beacon: &T) -> usize {
+        self.rejected.get(beacon).copied().unwrap_or_default()
+    }
+
+    /// Forgets the recent samples of beacons for which `keep` returns false.
+    ///
+    /// Their rejection counts are kept for diagnostics; only beacons that had samples
+    /// rejected are counted, which keeps the map small.
+    pub fn retain(&mut self, keep: impl Fn(&T) -> bool) {
+        self.samples.retain(|beacon, _| keep(beacon));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_rejects_spike() {
+        let clock = ManualClock::default();
+        let mut hampel = Hampel::default();
+        for rssi in [-70, -72, -71, -69, -70, -71] {
+            assert!(hampel.accept(&Signal::new("a", -59, rssi, &clock)));
+        }
+
+        assert!(!hampel.accept(&Signal::new("a", -59, -55, &clock)));
+        assert!(hampel.accept(&Signal::new("a", -59, -73, &clock)));
+        assert_eq!(hampel.rejected_by(&"a"), 1);
+        assert_eq!(hampel.rejected(), 1);
+
+        // the count outlives the samples of a beacon gone out of range
+        hampel.retain(|_| false);
+        assert_eq!(hampel.rejected_by(&"a"), 1);
+    }
+
+    #[test]
+    fn test_follows_level_shift() {
+        let clock = ManualClock::default();
+        let mut hampel = Hampel::default();
+        for _ in 0..9 {
+            hampel.accept(&Signal::new("a", -59, -80, &clock));
+        }
+
+        let accepted = (0..9)
+            .map(|_| hampel.accept(&Signal::new("a", -59, -60, &clock)))
+            .collect::<Vec<_>>();
+
+        assert!(!accepted[0]);
+        assert!(accepted[8]);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::beacon::BeaconId;
+use crate::clock::{Clock, SystemClock};
+use crate::pipeline::{BoundedSender, Stage};
+use crate::signal::{Aggregator, Batch, Buffer, Hampel, Kalman, MotionDetector, Signal};
+use crossbeam_channel::{Receiver, select, tick};
+use log::{debug, error, info};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Decides when the processor hands a window over to the locator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emission {
+    /// Emit on every tick.
+    EveryTick,
+    /// Emit on a tick only if the set of heard beacons changed since the last emission.
+    OnChange,
+    /// Emit as soon as the given number of distinct beacons was heard since the last
+    /// emission. Ticks still emit whatever was heard, so sparse areas are not starved.
+    Beacons(usize),
+}
+
+/// Timing and sizing of the [`Processor`].
+#[derive(Clone)]
+pub struct ProcessorConfig {
+    tick: Duration,
+    window: Duration,
+    per_beacon: usize,
+    buffer_size: usize,
+    stack_size: usize,
+    emission: Emission,
+    outliers: Option<Hampel<BeaconId>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_secs(5),
+            window: Duration::from_secs(5),
+            per_beacon: 8,
+            buffer_size: 64,
+            stack_size: 8 * 1024, // 8 KB stack
+            emission: Emission::EveryTick,
+            outliers: Some(Hampel::default()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl ProcessorConfig {
+    /// Interval at which windows are emitted.
+    pub fn tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Age up to which a signal is part of the window.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Maximum number of signals kept per beacon.
+    pub fn per_beacon(mut self, per_beacon: usize) -> Self {
+        self.per_beacon = per_beacon;
+        self
+    }
+
+    /// Maximum number of signals kept in the buffer across all beacons.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Stack size of the processor thread in bytes.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = stack_size;
+        self
+    }
+
+    pub fn emission(mut self, emission: Emission) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Filter dropping implausible samples before they enter the buffer, `None` to
+    /// keep every sample.
+    pub fn outliers(mut self, outliers: Option<Hampel<BeaconId>>) -> Self {
+        self.outliers = outliers;
+        self
+    }
+
+    /// Clock the window expires by; must be the one the scanner stamps signals with.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+/// Collects the signals of the scanner and emits one aggregated signal per beacon heard
+/// within the window.
+pub struct Processor {
+    config: ProcessorConfig,
+    aggregator: Box<dyn Aggregator<BeaconId>>,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new(ProcessorConfig::default(), Kalman::default())
+    }
+}
+
+impl Processor {
+    pub fn new(config: ProcessorConfig, aggregator: impl Aggregator<BeaconId> + 'static) -> Self {
+        Self {
+            config,
+            aggregator: Box::new(aggregator),
+        }
+    }
+
+    /// Starts the processor thread.
+    ///
+    /// On stop, or once the scanner disconnects, the current window is flushed before
+    /// the thread returns.
+    pub fn start(
+        self,
+        rx_bluetooth: Receiver<Signal<BeaconId>>,
+        tx_signals: BoundedSender<Batch<BeaconId>>,
+    ) -> anyhow::Result<Stage> {
+        let stack_size = self.config.stack_size;
+        Stage::spawn("processor", stack_size, move |stop| {
+            let ticker = tick(self.config.tick);
+            let mut worker = Worker::new(self, tx_signals);
+
+            loop {
+                select! {
+                    recv(rx_bluetooth) -> signal => match signal {
+                        Ok(m) => worker.on_signal(m),
+                        Err(_) => {
+                            info!("scanner disconnected, stopping processor");
+                            break;
+                        }
+                    },
+                    recv(ticker) -> _ => worker.on_tick(),
+                    recv(stop) -> _ => break,
+                }
+            }
+
+            worker.flush();
+        })
+    }
+}
+
+/// State of a running processor thread.
+struct Worker {
+    config: ProcessorConfig,
+    aggregator: Box<dyn Aggregator<BeaconId>>,
+    buffer: Buffer<BeaconId>,
+    emitter: Emitter,
+    outliers: Option<Hampel<BeaconId>>,
+    motion: MotionDetector<BeaconId>,
+    tx_signals: BoundedSender<Batch<BeaconId>>,
+}
+
+impl Worker {
+    fn new(processor: Processor, tx_signals: BoundedSender<Batch<BeaconId>>) -> Self {
+        let Processor {
+            mut config,
+            aggregator,
+        } = processor;
+
+        Self {
+            buffer: Buffer::new(config.per_beacon, config.buffer_size, config.clock.clone()),
+            emitter: Emitter::new(config.emission),
+            outliers: config.outliers.take(),
+            motion: MotionDetector::default(),
+            aggregator,
+            config,
+            tx_signals,
+        }
+    }
+
+    fn on_signal(&mut self, signal: Signal<BeaconId>) {
+        if self.outliers.as_mut().is_some_and(|o| !o.accept(&signal)) {
+            debug!("rejecting outlier {:?}", signal);
+            return;
+        }
+
+        info!("pushing signal {:?}", signal);
+        self.buffer.push(signal);
+
+        // collecting the window allocates, so only do it where it can lead to an emission
+        if !self.emitter.emits_on_signal() {
+            return;
+        }
+        let window = self.buffer.get_recent_signals(self.config.window);
+        if self.emitter.on_signal(&window) {
+            self.emit(&window);
+        }
+    }
+
+    fn on_tick(&mut self) {
+        self.buffer.expire(self.config.window);
+        if let Some(outliers) = self.outliers.as_mut() {
+            outliers.retain(|b| self.buffer.history(b).next().is_some());
+            info!("rejected {} outliers so far", outliers.rejected());
+        }
+        info!("batch queue {}", self.tx_signals.stats());
+
+        let window = self.buffer.get_recent_signals(self.config.window);
+        if self.emitter.on_tick(&window) {
+            self.emit(&window);
+        }
+    }
+
+    /// Emits whatever the window still holds.
+    fn flush(&mut self) {
+        let window = self.buffer.get_recent_signals(self.config.window);
+        if !window.is_empty() {
+            self.emit(&window);
+        }
+    }
+
+    fn emit(&mut self, window: &[Signal<BeaconId>]) {
+        let signals: Vec<_> = self
+            .aggregator
+            .aggregate(window)
+            .into_iter()
+            .map(|s| match self.buffer.stats(&s.beacon, self.config.window) {
+                Some(mut stats) => {
+                    stats.rejected = self
+                        .outliers
+                        .as_ref()
+                        .map_or(0, |o| o.rejected_by(&s.beacon));
+                    s.with_stats(stats)
+                }
+                None => s,
+            })
+            .collect();
+
+        let motion = self.motion.update(&signals);
+        debug!("motion state {:?}", motion);
+
+        if let Err(e) = self.tx_signals.send(Batch::new(signals, motion)) {
+            error!("error sending signals: {:?}", e);
+        }
+    }
+}
+
+/// Applies the [`Emission`] policy to the windows seen by the processor.
+struct Emitter {
+    emission: Emission,
+    emitted: HashSet<BeaconId>,
+    /// Monotonic time of the newest signal emitted so far.
+    emitted_at: Option<Duration>,
+}
+
+impl Emitter {
+    fn new(emission: Emission) -> Self {
+        Self {
+            emission,
+            emitted: HashSet::new(),
+            emitted_at: None,
+        }
+    }
+
+    /// Whether a signal, rather than only a tick, can trigger an emission.
+    fn emits_on_signal(&self) -> bool {
+        matches!(self.emission, Emission::Beacons(_))
+    }
+
+    fn on_signal(&mut self, window: &[Signal<BeaconId>]) -> bool {
+        match self.emission {
+            Emission::Beacons(n) => {
+                let heard = Self::beacons(window.iter().filter(|s| self.is_new(s)));
+                heard.len() >= n && self.emit(window)
+            }
+            Emission::EveryTick | Emission::OnChange => false,
+        }
+    }
+
+    fn on_tick(&mut self, window: &[Signal<BeaconId>]) -> bool {
+        match self.emission {
+            Emission::EveryTick => self.emit(window),
+            Emission::OnChange => {
+                if Self::beacons(window.iter()) == self.emitted {
+                    debug!("beacon set unchanged, skipping emission");
+                    false
+                } else {
+                    self.emit(window)
+                }
+            }
+            Emission::Beacons(_) => window.iter().any(|s| self.is_new(s)) && self.emit(window),
+        }
+    }
+
+    fn emit(&mut self, window: &[Signal<BeaconId>]) -> bool {
+        self.emitted = Self::beacons(window.iter());
+        self.emitted_at = window
+            .iter()
+            .map(|s| s.rx_monotonic)
+            .max()
+            .or(self.emitted_at);
+        true
+    }
+
+    fn is_new(&self, signal: &Signal<BeaconId>) -> bool {
+        self.emitted_at.is_none_or(|t| signal.rx_monotonic > t)
+    }
+
+    fn beacons<'a>(signals: impl Iterator<Item = &'a Signal<BeaconId>>) -> HashSet<BeaconId> {
+        signals.map(|s| s.beacon.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn signal(clock: &ManualClock, minor: u16) -> Signal<BeaconId> {
+        clock.advance(Duration::from_millis(100));
+        Signal::new(BeaconId::new("uuid", 0, minor), -59, -70, clock)
+    }
+
+    #[test]
+    fn test_on_change_skips_same_beacons() {
+        let clock = ManualClock::default();
+        let mut emitter = Emitter::new(Emission::OnChange);
+        let window = vec![signal(&clock, 1), signal(&clock, 2)];
+
+        assert!(!emitter.emits_on_signal());
+        assert!(emitter.on_tick(&window));
+        assert!(!emitter.on_tick(&window));
+        assert!(emitter.on_tick(&[signal(&clock, 1)]));
+    }
+
+    #[test]
+    fn test_beacons_emits_once_threshold_reached() {
+        let clock = ManualClock::default();
+        let mut emitter = Emitter::new(Emission::Beacons(3));
+        let mut window = vec![signal(&clock, 1), signal(&clock, 2), signal(&clock, 2)];
+
+        assert!(emitter.emits_on_signal());
+        assert!(!emitter.on_signal(&window));
+        window.push(signal(&clock, 3));
+        assert!(emitter.on_signal(&window));
+        assert!(!emitter.on_signal(&window));
+        window.push(signal(&clock, 1));
+        assert!(!emitter.on_signal(&window));
+    }
+}